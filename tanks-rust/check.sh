@@ -0,0 +1,14 @@
+#!/bin/bash
+
+# Runs the checks which every change to the game module crates must pass.
+
+set -e
+
+manifest=game-module/Cargo.toml
+
+cargo build --manifest-path $manifest --workspace
+cargo clippy --manifest-path $manifest --workspace --all-targets -- -D warnings
+cargo test --manifest-path $manifest --workspace
+
+# arete_public must also build without std, for tools and headless crates.
+cargo build --manifest-path $manifest -p arete_public --no-default-features --features libm
@@ -0,0 +1,936 @@
+use std::{
+    error::Error,
+    ffi::{c_char, c_void, CStr},
+    fmt,
+    marker::PhantomData,
+    mem::size_of,
+};
+
+use crate::{
+    AssetId, Component, ComponentId, EntityId, Name, RenderTarget, Resource, ScreenPosition,
+    Transform, Vec3,
+};
+
+/// A generic reference to a component. This type is necessary to pass components to `Engine::spawn()`
+/// and `Engine::set_components()`.
+///
+/// It is NOT recommended to use this struct manually -- use the `bundle!()` macro to automatically convert components.
+#[repr(C)]
+pub struct ComponentRef<'a> {
+    pub component_id: ComponentId,
+    pub component_size: usize,
+    pub component_val: *const c_void,
+    marker: PhantomData<&'a u8>,
+}
+
+impl<'a, C> From<&'a C> for ComponentRef<'a>
+where
+    C: Component,
+{
+    fn from(value: &'a C) -> Self {
+        Self {
+            component_id: C::id(),
+            component_size: size_of::<C>(),
+            component_val: value as *const C as *const c_void,
+            marker: PhantomData,
+        }
+    }
+}
+
+impl<'a, C> From<&'a mut C> for ComponentRef<'a>
+where
+    C: Component,
+{
+    fn from(value: &'a mut C) -> Self {
+        (&*value).into()
+    }
+}
+
+/// Converts a list of component references into the type which `Engine::spawn()` accepts.
+#[macro_export]
+macro_rules! bundle {
+    ($($c:expr),* $(,)?) => (
+        &[$(::arete_public::ComponentRef::from($c)),*]
+    )
+}
+
+/// Configuration for a burst of particles, spawned with `Engine::spawn_particles()`.
+#[repr(C, align(16))]
+#[derive(Clone, Copy, Debug)]
+pub struct ParticleBurst {
+    /// The static mesh used for every particle.
+    pub mesh: AssetId,
+    /// The number of particles to spawn.
+    pub count: u32,
+    /// The world space position where every particle starts.
+    pub origin: Vec3,
+    /// Each particle starts with a random velocity, uniformly distributed in the range
+    /// `[-velocity_spread, velocity_spread]` on each axis.
+    pub velocity_spread: Vec3,
+    /// The color of every particle.
+    pub color: Vec3,
+    /// The uniform scale applied to the mesh of every particle.
+    pub scale: f32,
+    /// The time in seconds before each particle is despawned.
+    pub lifetime: f32,
+    /// If `true`, every particle is spawned as a regular entity (with `Transform`, `Color`, and
+    /// `DynamicStaticMesh` components) which can be iterated by queries. Otherwise, particles
+    /// are simulated entirely by the engine and are not visible to queries.
+    pub queryable: bool,
+}
+
+/// The current version of the prefab format read by `Engine::spawn_prefab()`.
+///
+/// A prefab is little-endian binary data:
+///
+/// - `u32` format version
+/// - `u32` number of components, followed by each component:
+///   - the component's nul-terminated string id (see `Component::string_id()`)
+///   - `u32` number of fields, followed by each field:
+///     - the field's nul-terminated name
+///     - `u8` field kind (the generated `FieldKind` discriminant)
+///     - the field value: 1 byte for `bool`, 12 bytes (`x`, `y`, `z`) for `Vec3`, 16 bytes
+///       (`x`, `y`, `z`, `w`) for `Quat`, and the size of the underlying integer or float
+///       otherwise.
+///
+/// The version is incremented whenever the format changes. The engine reads every version up
+/// to the one it was built with.
+pub const PREFAB_FORMAT_VERSION: u32 = 1;
+
+/// The format of an asset loaded from memory with `Engine::load_asset_bytes()`. New formats
+/// must be appended, since the engine relies on the discriminants.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AssetFormat {
+    /// A binary glTF static mesh.
+    Glb,
+    /// A PNG image.
+    Png,
+    /// A JPEG image.
+    Jpeg,
+    /// A WAV audio clip.
+    Wav,
+}
+
+/// An error returned by `Engine::save_world()` or `Engine::load_world()`.
+#[repr(u32)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SaveError {
+    /// The file could not be read or written.
+    Io = 1,
+    /// The file is not a valid world snapshot.
+    Malformed = 2,
+    /// The file was saved by a module whose `ENGINE_VERSION` is not compatible with this one
+    /// (see `api_version_compatible()`).
+    UnsupportedVersion = 3,
+}
+
+impl SaveError {
+    fn from_code(code: u32) -> Result<(), Self> {
+        match code {
+            0 => Ok(()),
+            2 => Err(Self::Malformed),
+            3 => Err(Self::UnsupportedVersion),
+            _ => Err(Self::Io),
+        }
+    }
+}
+
+impl fmt::Display for SaveError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Io => "failed to read or write the world snapshot",
+            Self::Malformed => "malformed world snapshot",
+            Self::UnsupportedVersion => "world snapshot has an incompatible engine version",
+        })
+    }
+}
+
+impl Error for SaveError {}
+
+/// Collects the entities of an engine lookup which writes up to `capacity` entities and returns
+/// the total count. The first call only counts, since lookups are backed by indices which only
+/// change at the end of the frame, so the count stays valid for the second call.
+fn collect_entities(lookup: impl Fn(*mut EntityId, usize) -> usize) -> Vec<EntityId> {
+    let count = lookup(std::ptr::null_mut(), 0);
+
+    let mut entities = Vec::with_capacity(count);
+
+    unsafe {
+        let len = lookup(entities.as_mut_ptr(), count);
+        entities.set_len(len.min(count));
+    }
+
+    entities
+}
+
+/// Rejects a non-finite transform written through `Engine` in debug builds, with the
+/// `check-finite` feature.
+#[cfg(feature = "check-finite")]
+fn check_finite(transform: &Transform) {
+    debug_assert!(
+        transform.is_finite(),
+        "writing a non-finite transform: {transform:?}",
+    );
+}
+
+/// A resource which exposes engine functionality, like spawning and despawning.
+///
+/// Spawning, despawning, and adding or removing components are deferred: each command is queued and applied
+/// at the end of the frame. Commands issued from the same thread are applied in the order they
+/// were issued (commands issued from different threads, i.e. within `par_for_each`, have no
+/// ordering guarantee between threads). Since `spawn()` returns the `EntityId` immediately,
+/// commands may target an entity spawned on the same frame. For example, `add_component()` on a
+/// freshly-spawned entity is applied after the spawn, so the entity appears on the next frame
+/// with all of its components. Commands targeting an entity despawned earlier in the queue are
+/// ignored.
+#[repr(C)]
+#[derive(Resource)]
+pub struct Engine {
+    engine_handle: *const c_void,
+    spawn: unsafe extern "C" fn(*const c_void, *const ComponentRef, usize) -> EntityId,
+    despawn: unsafe extern "C" fn(*const c_void, EntityId),
+    set_component_value: unsafe extern "C" fn(*const c_void, *const c_void, *const ComponentRef),
+    load_asset: unsafe extern "C" fn(*const c_void, *const c_char) -> AssetId,
+    despawn_recursive: unsafe extern "C" fn(*const c_void, EntityId),
+    asset_reloaded: unsafe extern "C" fn(*const c_void, AssetId) -> bool,
+    add_component: unsafe extern "C" fn(*const c_void, EntityId, *const ComponentRef),
+    remove_component: unsafe extern "C" fn(*const c_void, EntityId, ComponentId),
+    asset_bounds: unsafe extern "C" fn(*const c_void, AssetId, *mut Vec3, *mut Vec3) -> bool,
+    asset_triangle_count: unsafe extern "C" fn(*const c_void, AssetId, *mut usize) -> bool,
+    spawn_particles: unsafe extern "C" fn(*const c_void, *const ParticleBurst),
+    set_system_enabled: unsafe extern "C" fn(*const c_void, *const c_char, bool),
+    spawn_prefab: unsafe extern "C" fn(*const c_void, *const u8, usize, *mut EntityId) -> bool,
+    set_clear_color: unsafe extern "C" fn(*const c_void, *const Vec3),
+    create_render_target: unsafe extern "C" fn(*const c_void, u32, u32, *mut AssetId) -> u32,
+    save_world: unsafe extern "C" fn(*const c_void, *const c_char) -> u32,
+    load_world: unsafe extern "C" fn(*const c_void, *const c_char) -> u32,
+    set_window_icon: unsafe extern "C" fn(*const c_void, AssetId),
+    set_cursor_image: unsafe extern "C" fn(*const c_void, AssetId, *const ScreenPosition),
+    set_transforms: unsafe extern "C" fn(*const c_void, *const EntityId, *const Transform, usize),
+    find_by_name: unsafe extern "C" fn(*const c_void, *const Name, *mut EntityId, usize) -> usize,
+    reserve: unsafe extern "C" fn(*const c_void, *const ComponentId, usize, usize),
+    add_trauma: unsafe extern "C" fn(*const c_void, f32),
+    entities_in_radius:
+        unsafe extern "C" fn(*const c_void, *const Vec3, f32, *mut EntityId, usize) -> usize,
+    entities_in_box: unsafe extern "C" fn(
+        *const c_void,
+        *const Vec3,
+        *const Vec3,
+        *mut EntityId,
+        usize,
+    ) -> usize,
+    set_gamepad_rumble: unsafe extern "C" fn(*const c_void, u32, f32, f32, f32),
+    detach_children: unsafe extern "C" fn(*const c_void, EntityId),
+    world_checksum: unsafe extern "C" fn(*const c_void) -> u64,
+    set_components:
+        unsafe extern "C" fn(*const c_void, EntityId, *const ComponentRef, usize) -> bool,
+    load_asset_bytes:
+        unsafe extern "C" fn(*const c_void, AssetFormat, *const u8, usize, *mut AssetId) -> bool,
+}
+
+impl Default for Engine {
+    fn default() -> Self {
+        unreachable!("cannot construct Engine resource")
+    }
+}
+
+unsafe impl Send for Engine {}
+unsafe impl Sync for Engine {}
+
+impl Engine {
+    /// Spawns an entity with the specified components.
+    ///
+    /// Returns the `EntityId` of the new entity.
+    ///
+    /// NOTE: spawns are deferred until the end of the frame, so the spawned entity will
+    /// not be iterated by queries on the frame it is spawned.
+    ///
+    /// The engine checks that the components include every component they require (see
+    /// `Component::requires()`), i.e. a `DynamicStaticMesh` must be spawned with a `Transform`.
+    pub fn spawn(&self, components: &[ComponentRef]) -> EntityId {
+        #[cfg(feature = "check-finite")]
+        for component in components {
+            if component.component_id == Transform::id() {
+                check_finite(unsafe { &*(component.component_val as *const Transform) });
+            }
+        }
+
+        unsafe { (self.spawn)(self.engine_handle, components.as_ptr(), components.len()) }
+    }
+
+    /// Preallocates storage for `additional` more entities with exactly the components in
+    /// `components` (i.e. `&[Transform::id(), Color::id(), DynamicStaticMesh::id()]`), so that a
+    /// spawn loop doesn't repeatedly grow the storage.
+    ///
+    /// This is only a performance hint, with no effect on correctness: spawning more entities
+    /// than reserved, or with other components, still works. The order of `components` doesn't
+    /// matter.
+    pub fn reserve(&self, components: &[ComponentId], additional: usize) {
+        unsafe {
+            (self.reserve)(
+                self.engine_handle,
+                components.as_ptr(),
+                components.len(),
+                additional,
+            );
+        }
+    }
+
+    /// Spawns an entity from a serialized prefab (see `PREFAB_FORMAT_VERSION` for the format).
+    ///
+    /// Returns the `EntityId` of the new entity, or `None` if the prefab is malformed or has an
+    /// unsupported format version, in which case nothing is spawned.
+    ///
+    /// Components with an unknown string id are skipped with a warning, so prefabs may outlive
+    /// the components they reference. Fields are matched by name using the component reflection
+    /// metadata: prefab fields the component doesn't have are skipped, and component fields
+    /// missing from the prefab are zeroed.
+    ///
+    /// NOTE: like `spawn()`, this is deferred until the end of the frame.
+    pub fn spawn_prefab(&self, bytes: &[u8]) -> Option<EntityId> {
+        let mut entity_id = EntityId(0);
+
+        unsafe {
+            (self.spawn_prefab)(
+                self.engine_handle,
+                bytes.as_ptr(),
+                bytes.len(),
+                &mut entity_id,
+            )
+        }
+        .then_some(entity_id)
+    }
+
+    /// Saves every entity, with all of its components, to the file at `path`.
+    ///
+    /// The snapshot starts with the `ENGINE_VERSION` of the module which saved it, followed by the
+    /// number of entities, each serialized as a prefab without the version header (see
+    /// `PREFAB_FORMAT_VERSION`). Resources are not saved.
+    ///
+    /// The snapshot reflects the world at the start of the frame: deferred commands issued this
+    /// frame are not included.
+    pub fn save_world(&self, path: &CStr) -> Result<(), SaveError> {
+        SaveError::from_code(unsafe { (self.save_world)(self.engine_handle, path.as_ptr()) })
+    }
+
+    /// Replaces every entity with the ones saved by `save_world()` to the file at `path`.
+    ///
+    /// Fails with `SaveError::UnsupportedVersion` if the snapshot was saved with an `ENGINE_VERSION`
+    /// which is not compatible with this module's (see `api_version_compatible()`), in which case
+    /// the world is left untouched. As with `spawn_prefab()`, components with an unknown string id
+    /// are skipped and logged, and fields are matched by name. Loaded entities get new `EntityId`s,
+    /// and `EntityId` fields (i.e. `Parent`) are remapped to them.
+    ///
+    /// NOTE: the file is read immediately, but the world is replaced at the end of the frame.
+    pub fn load_world(&self, path: &CStr) -> Result<(), SaveError> {
+        SaveError::from_code(unsafe { (self.load_world)(self.engine_handle, path.as_ptr()) })
+    }
+
+    /// Returns a hash of all component data in the world, i.e. to detect desyncs between peers
+    /// in lockstep networking, or between a recording and its replay.
+    ///
+    /// Entities are hashed in `EntityId` order, and each entity's components in `ComponentId`
+    /// order, so two worlds with the same entities and component values produce the same checksum
+    /// regardless of archetype layout. Resources are not included. Like `save_world()`, this
+    /// reflects the world at the start of the frame.
+    ///
+    /// NOTE: matching checksums require the simulation itself to be deterministic, i.e. entities
+    /// spawned in the same order and systems which do not depend on `par_for_each()` iteration
+    /// order. Component data is hashed bit for bit, so floating-point results which differ across
+    /// platforms or compilers (i.e. from `sin()` or fused multiply-adds) cause mismatches even when
+    /// the simulations agree; storing networked state as fixed-point integers avoids this.
+    pub fn world_checksum(&self) -> u64 {
+        unsafe { (self.world_checksum)(self.engine_handle) }
+    }
+
+    /// Despawns an entity with the specified `EntityId`.
+    ///
+    /// NOTE: despawns are deferred until the end of the frame, so the despawned entity will
+    /// still be iterated by queries on the frame it is despawned.
+    pub fn despawn(&self, entity_id: EntityId) {
+        unsafe {
+            (self.despawn)(self.engine_handle, entity_id);
+        }
+    }
+
+    /// Adds a component to an entity, replacing the value if the entity already has the component.
+    ///
+    /// NOTE: adding components is deferred until the end of the frame (see `Engine` docs for
+    /// ordering). This may be used with an entity spawned on the same frame.
+    pub fn add_component<C: Component>(&self, entity_id: EntityId, component: &C) {
+        #[cfg(feature = "check-finite")]
+        if C::id() == Transform::id() {
+            check_finite(unsafe { &*(component as *const C as *const Transform) });
+        }
+
+        unsafe {
+            (self.add_component)(
+                self.engine_handle,
+                entity_id,
+                &ComponentRef::from(component),
+            );
+        }
+    }
+
+    /// Removes component `C` from an entity. Does nothing if the entity does not have the component.
+    ///
+    /// Systems may react to the removal on the following frame using `Removed<C>`.
+    ///
+    /// NOTE: removing components is deferred until the end of the frame (see `Engine` docs for
+    /// ordering).
+    pub fn remove_component<C: Component>(&self, entity_id: EntityId) {
+        unsafe {
+            (self.remove_component)(self.engine_handle, entity_id, C::id());
+        }
+    }
+
+    /// Overwrites several components of an entity in a single call, i.e. to apply a networked
+    /// snapshot: `engine.set_components(entity_id, bundle!(&transform, &velocity))`.
+    ///
+    /// Either every component is written, or none are: if the entity does not exist or lacks any
+    /// of the components, nothing is written and `false` is returned. Components are never added,
+    /// since that changes the entity's archetype; use `add_component()` for those.
+    ///
+    /// NOTE: like `set_transforms()`, this is applied immediately rather than deferred. It must
+    /// not be called from inside the iteration of a query which accesses any of the components.
+    pub fn set_components(&self, entity_id: EntityId, components: &[ComponentRef]) -> bool {
+        #[cfg(feature = "check-finite")]
+        for component in components {
+            if component.component_id == Transform::id() {
+                check_finite(unsafe { &*(component.component_val as *const Transform) });
+            }
+        }
+
+        unsafe {
+            (self.set_components)(
+                self.engine_handle,
+                entity_id,
+                components.as_ptr(),
+                components.len(),
+            )
+        }
+    }
+
+    /// Sets the `Transform` of each entity in `entities` to the transform at the same index in
+    /// `transforms`, i.e. to teleport many entities at once for a level reset. Entities which do
+    /// not exist or have no `Transform` are skipped.
+    ///
+    /// This is much faster than setting each transform separately, since all of them cross the
+    /// FFI boundary in a single call.
+    ///
+    /// NOTE: unlike most commands, this is applied immediately rather than deferred. It must not
+    /// be called from inside the iteration of a query which accesses `Transform`.
+    ///
+    /// Panics if `entities` and `transforms` have different lengths.
+    pub fn set_transforms(&self, entities: &[EntityId], transforms: &[Transform]) {
+        assert_eq!(
+            entities.len(),
+            transforms.len(),
+            "entities and transforms must have the same length",
+        );
+
+        #[cfg(feature = "check-finite")]
+        transforms.iter().for_each(check_finite);
+
+        unsafe {
+            (self.set_transforms)(
+                self.engine_handle,
+                entities.as_ptr(),
+                transforms.as_ptr(),
+                entities.len(),
+            );
+        }
+    }
+
+    /// Spawns a burst of particles. The engine moves each particle by its velocity (under
+    /// gravity), and despawns it once its lifetime has elapsed.
+    ///
+    /// This is much faster than spawning each particle with `spawn()`, since the whole burst
+    /// crosses the FFI boundary in a single call. Particles are not visible to queries unless
+    /// `ParticleBurst::queryable` is set.
+    ///
+    /// NOTE: like spawns, particle bursts are deferred until the end of the frame.
+    pub fn spawn_particles(&self, config: ParticleBurst) {
+        unsafe {
+            (self.spawn_particles)(self.engine_handle, &config);
+        }
+    }
+
+    /// Enables or disables the system with the specified function name (i.e. `c"camera_update"`).
+    /// The engine skips disabled systems. All systems are enabled by default.
+    ///
+    /// NOTE: this takes effect on the next frame, so a system disabled mid-frame may still run
+    /// on the current frame. A `#[system_once]` system which has already run will not run
+    /// again when re-enabled.
+    pub fn set_system_enabled(&self, name: &CStr, enabled: bool) {
+        unsafe {
+            (self.set_system_enabled)(self.engine_handle, name.as_ptr(), enabled);
+        }
+    }
+
+    /// Despawns an entity with the specified `EntityId`, along with all of its descendants
+    /// (every entity whose chain of `Parent` components leads to this entity).
+    ///
+    /// Each entity is despawned at most once, so a malformed hierarchy containing a cycle
+    /// will not cause an infinite loop; every entity in the cycle is despawned.
+    ///
+    /// NOTE: despawns are deferred until the end of the frame, so the despawned entities will
+    /// still be iterated by queries on the frame they are despawned.
+    pub fn despawn_recursive(&self, entity_id: EntityId) {
+        unsafe {
+            (self.despawn_recursive)(self.engine_handle, entity_id);
+        }
+    }
+
+    /// Removes the `Parent` component of every direct child of `parent`, keeping each child where
+    /// it is in the world, i.e. so that a parent can be despawned without its children popping
+    /// to the origin.
+    ///
+    /// Since a child's `Transform` is relative to its parent's, the engine bakes the child's world
+    /// transform into its `Transform`: it composes the transforms along the chain of parents
+    /// into the child's world matrix, and decomposes that into position, rotation, and scale.
+    /// When a parent with non-uniform scale is rotated relative to the child, the world matrix
+    /// contains shear, which `Transform` cannot represent: the baked scale is then the length of
+    /// each axis of the world matrix, so the child keeps its position and orientation, but its
+    /// shape may change slightly. Grandchildren keep their `Parent`, and so move with the
+    /// detached child as before.
+    ///
+    /// NOTE: like `remove_component()`, this is deferred until the end of the frame. The world
+    /// transforms are computed at that point, so transform changes made this frame are included.
+    pub fn detach_children(&self, parent: EntityId) {
+        unsafe {
+            (self.detach_children)(self.engine_handle, parent);
+        }
+    }
+
+    /// Returns an entity whose `Name` is `name`, or `None` if there is none. If several entities
+    /// have the name, which of them is returned is unspecified (see `find_all_by_name()`).
+    ///
+    /// Names are compared byte for byte, so the lookup is case-sensitive. `name` is truncated to
+    /// `Name::MAX_LEN` bytes, like `Name::new()`. The engine maintains an index of names, updated
+    /// at the end of each frame, so entities spawned or renamed this frame are not found until
+    /// the next frame.
+    pub fn find_by_name(&self, name: &CStr) -> Option<EntityId> {
+        let mut entity_id = EntityId(0);
+
+        let count =
+            unsafe { (self.find_by_name)(self.engine_handle, &Name::new(name), &mut entity_id, 1) };
+
+        (count > 0).then_some(entity_id)
+    }
+
+    /// Returns every entity whose `Name` is `name`, in unspecified order. See `find_by_name()`
+    /// for how names are compared.
+    pub fn find_all_by_name(&self, name: &CStr) -> Vec<EntityId> {
+        let name = Name::new(name);
+
+        collect_entities(|entities, capacity| unsafe {
+            (self.find_by_name)(self.engine_handle, &name, entities, capacity)
+        })
+    }
+
+    /// Returns every entity whose `Transform::position` is within `radius` of `center`, in
+    /// unspecified order, i.e. the entities caught in an explosion.
+    ///
+    /// This is backed by a spatial index which the engine rebuilds at the end of each frame, so
+    /// the results reflect last frame's transforms: entities spawned or moved this frame are
+    /// found at their previous positions, if at all. Entities without a `Transform` are excluded.
+    pub fn entities_in_radius(&self, center: Vec3, radius: f32) -> Vec<EntityId> {
+        collect_entities(|entities, capacity| unsafe {
+            (self.entities_in_radius)(self.engine_handle, &center, radius, entities, capacity)
+        })
+    }
+
+    /// Returns every entity whose `Transform::position` is inside the axis-aligned box from `min`
+    /// to `max` (inclusive), in unspecified order. See `entities_in_radius()` for how the spatial
+    /// index is updated.
+    pub fn entities_in_box(&self, min: Vec3, max: Vec3) -> Vec<EntityId> {
+        collect_entities(|entities, capacity| unsafe {
+            (self.entities_in_box)(self.engine_handle, &min, &max, entities, capacity)
+        })
+    }
+
+    /// Loads an asset (a static mesh, or an image). It is safe to call this for the same asset
+    /// multiple times.
+    ///
+    /// Returns the identifying `AssetId` of the newly-loaded asset. If the asset has already been
+    /// loaded, it will return the `AssetId` of the originally-loaded asset.
+    pub fn load_asset(&self, asset_path: &CStr) -> AssetId {
+        unsafe { (self.load_asset)(self.engine_handle, asset_path.as_ptr()) }
+    }
+
+    /// Loads an asset from the encoded file contents in `bytes`, i.e. downloaded content or an
+    /// image generated at runtime. The engine copies `bytes`, so the buffer may be dropped as
+    /// soon as this returns.
+    ///
+    /// Unlike `load_asset()`, every call creates a new asset. Returns the `AssetId` of the new
+    /// asset, or `None` if `bytes` could not be decoded as `format`, or the engine doesn't support
+    /// `format`.
+    pub fn load_asset_bytes(&self, format: AssetFormat, bytes: &[u8]) -> Option<AssetId> {
+        let mut asset_id = AssetId(0);
+
+        unsafe {
+            (self.load_asset_bytes)(
+                self.engine_handle,
+                format,
+                bytes.as_ptr(),
+                bytes.len(),
+                &mut asset_id,
+            )
+        }
+        .then_some(asset_id)
+    }
+
+    /// Sets the RGB color which the screen is cleared to before rendering, i.e. the background
+    /// wherever no geometry is drawn. Until this is called, the engine's default color is used.
+    ///
+    /// A skybox, when the engine draws one, covers the clear color entirely.
+    pub fn set_clear_color(&self, color: Vec3) {
+        unsafe {
+            (self.set_clear_color)(self.engine_handle, &color);
+        }
+    }
+
+    /// Sets the window icon to an image asset loaded with `load_asset()` (PNG or JPEG).
+    ///
+    /// If the asset failed to load or is not an image, the engine logs a warning and keeps the
+    /// current icon. Some platforms ignore the window icon: macOS and mobile platforms use the
+    /// application bundle's icon, and most Wayland compositors use the desktop entry's icon.
+    pub fn set_window_icon(&self, asset: AssetId) {
+        unsafe {
+            (self.set_window_icon)(self.engine_handle, asset);
+        }
+    }
+
+    /// Replaces the mouse cursor with an image asset loaded with `load_asset()` (PNG or JPEG).
+    ///
+    /// `hotspot` is the point of the image which the cursor points with, where the top-left of
+    /// the image is `(0, 0)` and the bottom-right is `(1, 1)` (i.e. `ScreenPosition::CENTER`
+    /// for a crosshair).
+    ///
+    /// If the asset failed to load or is not an image, the engine logs a warning and keeps the
+    /// current cursor. Platforms without a mouse cursor (i.e. mobile platforms) ignore this, and
+    /// some platforms limit the cursor size, scaling down larger images.
+    pub fn set_cursor_image(&self, asset: AssetId, hotspot: ScreenPosition) {
+        unsafe {
+            (self.set_cursor_image)(self.engine_handle, asset, &hotspot);
+        }
+    }
+
+    /// Adds `amount` of trauma, which drives engine-side camera shake, i.e. for impact feedback.
+    ///
+    /// Trauma is clamped to `[0, 1]`, and decays linearly by 1 per second. Each frame, every
+    /// camera is offset and rotated by noise scaled by the square of the current trauma, so small
+    /// amounts barely shake while large amounts shake hard, and the shake eases out as trauma
+    /// decays. The shake is applied when rendering, so it does not change any `Transform`.
+    pub fn add_trauma(&self, amount: f32) {
+        unsafe {
+            (self.add_trauma)(self.engine_handle, amount);
+        }
+    }
+
+    /// Rumbles the gamepad with index `gamepad` for `duration` seconds. `low_freq` and
+    /// `high_freq` are the intensities of the low and high frequency motors, clamped to `[0, 1]`.
+    ///
+    /// This is fire-and-forget: a new call replaces any rumble still playing on the gamepad, and
+    /// an intensity of 0 stops the motor. Does nothing if the gamepad isn't connected or has no
+    /// rumble motors, or on platforms without rumble support.
+    pub fn set_gamepad_rumble(&self, gamepad: u32, low_freq: f32, high_freq: f32, duration: f32) {
+        unsafe {
+            (self.set_gamepad_rumble)(self.engine_handle, gamepad, low_freq, high_freq, duration);
+        }
+    }
+
+    /// Creates an offscreen render target of `width` by `height` pixels.
+    ///
+    /// Returns the `RenderTarget` component which makes a camera render to it, and the `AssetId`
+    /// of the texture holding its contents, e.g. for drawing a minimap or mirror.
+    pub fn create_render_target(&self, width: u32, height: u32) -> (RenderTarget, AssetId) {
+        let mut asset_id = AssetId(0);
+
+        let target = unsafe {
+            (self.create_render_target)(self.engine_handle, width, height, &mut asset_id)
+        };
+
+        (RenderTarget(target), asset_id)
+    }
+
+    /// Returns `true` if the engine detected a change to the asset's file and reloaded it this frame.
+    ///
+    /// An asset keeps its `AssetId` when reloaded, so changes to geometry are transparent to
+    /// entities using the asset. However, any state derived from the asset (e.g. cached bounds)
+    /// may need to be recomputed.
+    pub fn asset_reloaded(&self, asset_id: AssetId) -> bool {
+        unsafe { (self.asset_reloaded)(self.engine_handle, asset_id) }
+    }
+
+    /// Returns the `(min, max)` corners of the asset's axis-aligned bounding box, in the mesh's
+    /// local space (i.e. before any `Transform` is applied).
+    ///
+    /// Returns `None` if the asset has not finished loading, or the `AssetId` is invalid.
+    pub fn asset_bounds(&self, asset_id: AssetId) -> Option<(Vec3, Vec3)> {
+        let mut min = Vec3::default();
+        let mut max = Vec3::default();
+
+        unsafe { (self.asset_bounds)(self.engine_handle, asset_id, &mut min, &mut max) }
+            .then_some((min, max))
+    }
+
+    /// Returns the number of triangles in the asset's mesh.
+    ///
+    /// Returns `None` if the asset has not finished loading, or the `AssetId` is invalid.
+    pub fn asset_triangle_count(&self, asset_id: AssetId) -> Option<usize> {
+        let mut count = 0;
+
+        unsafe { (self.asset_triangle_count)(self.engine_handle, asset_id, &mut count) }
+            .then_some(count)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{cell::RefCell, collections::BTreeMap, sync::MutexGuard};
+
+    use super::*;
+    use crate::Color;
+
+    /// A deferred command, as recorded by the fake engine.
+    #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+    enum Command {
+        Spawn(u64),
+        Despawn(u64),
+        Add(u64, ComponentId),
+        Remove(u64, ComponentId),
+    }
+
+    struct FakeEngine {
+        /// Held for the whole test, since the component ids are process-global.
+        _globals: MutexGuard<'static, ()>,
+        next_entity: RefCell<u64>,
+        queue: RefCell<Vec<Command>>,
+        /// The components of each entity, as of the last `apply`.
+        world: RefCell<BTreeMap<u64, Vec<ComponentId>>>,
+    }
+
+    impl FakeEngine {
+        fn new() -> Self {
+            let globals = crate::tests::lock_globals();
+
+            Transform::set_id(1);
+            Color::set_id(2);
+
+            Self {
+                _globals: globals,
+                next_entity: RefCell::new(1),
+                queue: RefCell::new(Vec::new()),
+                world: RefCell::new(BTreeMap::new()),
+            }
+        }
+
+        /// Applies the queued commands at the end of the frame, following the contract in the
+        /// `Engine` docs: in order, ignoring commands to entities which no longer exist.
+        fn apply(&self) {
+            let mut world = self.world.borrow_mut();
+
+            for command in self.queue.borrow_mut().drain(..) {
+                match command {
+                    Command::Spawn(entity) => {
+                        world.insert(entity, Vec::new());
+                    }
+                    Command::Despawn(entity) => {
+                        world.remove(&entity);
+                    }
+                    Command::Add(entity, component) => {
+                        if let Some(components) = world.get_mut(&entity) {
+                            if !components.contains(&component) {
+                                components.push(component);
+                            }
+                        }
+                    }
+                    Command::Remove(entity, component) => {
+                        if let Some(components) = world.get_mut(&entity) {
+                            components.retain(|c| *c != component);
+                        }
+                    }
+                }
+            }
+        }
+
+        fn components(&self, entity_id: EntityId) -> Option<Vec<ComponentId>> {
+            self.world.borrow().get(&entity_id.0).cloned()
+        }
+
+        fn engine(&self) -> Engine {
+            unsafe extern "C" fn unused() {
+                unreachable!("not used by these tests");
+            }
+
+            // Reinterprets `unused` as the function pointer type of any field. The fields it is
+            // used for are never called.
+            macro_rules! unused {
+                () => {
+                    unsafe {
+                        *(&(unused as unsafe extern "C" fn()) as *const unsafe extern "C" fn())
+                            .cast()
+                    }
+                };
+            }
+
+            unsafe fn fake<'a>(handle: *const c_void) -> &'a FakeEngine {
+                &*(handle as *const FakeEngine)
+            }
+
+            unsafe extern "C" fn spawn(
+                handle: *const c_void,
+                components: *const ComponentRef,
+                len: usize,
+            ) -> EntityId {
+                let fake = fake(handle);
+
+                let entity = fake.next_entity.replace_with(|id| *id + 1);
+                let mut queue = fake.queue.borrow_mut();
+
+                queue.push(Command::Spawn(entity));
+
+                for component in std::slice::from_raw_parts(components, len) {
+                    queue.push(Command::Add(entity, component.component_id));
+                }
+
+                EntityId(entity)
+            }
+
+            unsafe extern "C" fn despawn(handle: *const c_void, entity_id: EntityId) {
+                fake(handle)
+                    .queue
+                    .borrow_mut()
+                    .push(Command::Despawn(entity_id.0));
+            }
+
+            unsafe extern "C" fn add_component(
+                handle: *const c_void,
+                entity_id: EntityId,
+                component: *const ComponentRef,
+            ) {
+                fake(handle)
+                    .queue
+                    .borrow_mut()
+                    .push(Command::Add(entity_id.0, (*component).component_id));
+            }
+
+            unsafe extern "C" fn remove_component(
+                handle: *const c_void,
+                entity_id: EntityId,
+                component_id: ComponentId,
+            ) {
+                fake(handle)
+                    .queue
+                    .borrow_mut()
+                    .push(Command::Remove(entity_id.0, component_id));
+            }
+
+            Engine {
+                engine_handle: self as *const FakeEngine as *const c_void,
+                spawn,
+                despawn,
+                set_component_value: unused!(),
+                load_asset: unused!(),
+                despawn_recursive: unused!(),
+                asset_reloaded: unused!(),
+                add_component,
+                remove_component,
+                asset_bounds: unused!(),
+                asset_triangle_count: unused!(),
+                spawn_particles: unused!(),
+                set_system_enabled: unused!(),
+                spawn_prefab: unused!(),
+                set_clear_color: unused!(),
+                create_render_target: unused!(),
+                save_world: unused!(),
+                load_world: unused!(),
+                set_window_icon: unused!(),
+                set_cursor_image: unused!(),
+                set_transforms: unused!(),
+                find_by_name: unused!(),
+                reserve: unused!(),
+                add_trauma: unused!(),
+                entities_in_radius: unused!(),
+                entities_in_box: unused!(),
+                set_gamepad_rumble: unused!(),
+                detach_children: unused!(),
+                world_checksum: unused!(),
+                set_components: unused!(),
+                load_asset_bytes: unused!(),
+            }
+        }
+    }
+
+    #[test]
+    fn add_component_after_spawn_same_frame() {
+        let fake = FakeEngine::new();
+        let engine = fake.engine();
+
+        let entity_id = engine.spawn(&[ComponentRef::from(&Transform::default())]);
+        engine.add_component(entity_id, &Color::default());
+
+        // Nothing is applied until the end of the frame.
+        assert_eq!(fake.components(entity_id), None);
+
+        assert_eq!(
+            *fake.queue.borrow(),
+            [
+                Command::Spawn(entity_id.0),
+                Command::Add(entity_id.0, Transform::id()),
+                Command::Add(entity_id.0, Color::id()),
+            ],
+        );
+
+        fake.apply();
+
+        assert_eq!(
+            fake.components(entity_id),
+            Some(vec![Transform::id(), Color::id()]),
+        );
+    }
+
+    #[test]
+    fn add_and_remove_apply_in_issue_order() {
+        let fake = FakeEngine::new();
+        let engine = fake.engine();
+
+        let added_last = engine.spawn(&[ComponentRef::from(&Transform::default())]);
+        engine.remove_component::<Color>(added_last);
+        engine.add_component(added_last, &Color::default());
+
+        let removed_last = engine.spawn(&[ComponentRef::from(&Transform::default())]);
+        engine.add_component(removed_last, &Color::default());
+        engine.remove_component::<Color>(removed_last);
+
+        fake.apply();
+
+        assert_eq!(
+            fake.components(added_last),
+            Some(vec![Transform::id(), Color::id()]),
+        );
+        assert_eq!(fake.components(removed_last), Some(vec![Transform::id()]));
+    }
+
+    #[test]
+    fn commands_after_despawn_are_ignored() {
+        let fake = FakeEngine::new();
+        let engine = fake.engine();
+
+        let entity_id = engine.spawn(&[ComponentRef::from(&Transform::default())]);
+        engine.despawn(entity_id);
+        engine.add_component(entity_id, &Color::default());
+
+        fake.apply();
+
+        assert_eq!(fake.components(entity_id), None);
+        assert_eq!(
+            fake.queue.borrow().len(),
+            0,
+            "the queue is drained at the end of the frame",
+        );
+    }
+}
@@ -0,0 +1,47 @@
+use std::sync::Mutex;
+
+/// A double-buffered channel of events of type `T`, used to communicate between systems.
+///
+/// Events sent during a frame become readable on the *next* frame, regardless of the order in
+/// which the sending and receiving systems run. Events which are not drained on that frame are
+/// discarded.
+///
+/// Resources cannot be generic, so this type is not used as a resource directly. Instead, use
+/// the `define_event!()` macro, which defines the event type along with a resource wrapping
+/// `Events` (i.e. `define_event!(HitEvent { .. })` defines `HitEvent` and `HitEvents`).
+pub struct Events<T> {
+    sent: Mutex<Vec<T>>,
+    readable: Vec<T>,
+}
+
+impl<T> Default for Events<T> {
+    fn default() -> Self {
+        Self {
+            sent: Mutex::new(Vec::new()),
+            readable: Vec::new(),
+        }
+    }
+}
+
+impl<T: Copy + Send + Sync> Events<T> {
+    /// Sends an event. The event will be readable next frame.
+    pub fn send(&self, event: T) {
+        self.sent.lock().unwrap().push(event);
+    }
+
+    /// Returns all events sent last frame, removing them from the channel.
+    pub fn drain(&mut self) -> impl Iterator<Item = T> + '_ {
+        self.readable.drain(..)
+    }
+
+    /// Makes the events sent this frame readable, discarding any undrained events from the
+    /// previous frame.
+    ///
+    /// This is called once per frame by the system generated by `define_event!()`, and should
+    /// not be called manually.
+    pub fn update(&mut self) {
+        let sent = self.sent.get_mut().unwrap();
+        std::mem::swap(&mut self.readable, sent);
+        sent.clear();
+    }
+}
@@ -2,20 +2,68 @@
 //!
 //! This crate provides all the public types and traits of Arete.
 //! It is a Rust wrapper on top of Arete's C API.
+//!
+//! # Features
+//!
+//! - `std` (default): enables the engine FFI layer, i.e. `Engine`, `Query`, `Removed`,
+//!   `ComponentRef` and the `bundle!()` macro, as well as `Events` and `system_info()`. These
+//!   are only usable from a game module loaded by Arete.
+//! - `libm`: provides the float math used by `Vec3` and `Quat` when `std` is disabled. One of
+//!   `std` or `libm` must be enabled.
+//! - `check-finite`: in debug builds, panics whenever a non-finite `Transform` is written
+//!   through `Engine` (i.e. spawned, added, or set with `Engine::set_transforms()`), so the
+//!   engine reports the offending system. Writes through queries are not checked.
+//! - `test-util`: provides `MockQuery`, an in-memory `Query` for unit testing gameplay logic
+//!   without the engine. Enable it in `[dev-dependencies]` only.
+//!
+//! With `default-features = false, features = ["libm"]`, this crate is `no_std` and only
+//! exposes the math types (`Vec3`, `Quat`) and the plain value types (`Transform`, `Color`,
+//! `Camera`, the lights, `ScreenPosition`, `InputState`, ...), along with the `Component` and
+//! `Resource` traits. This allows tools and headless crates (e.g. physics or netcode) to share
+//! these types without linking the engine.
 
-use std::{
-    ffi::{c_char, c_int, c_void, CStr},
-    marker::PhantomData,
-    mem::{size_of, transmute_copy},
-    panic::catch_unwind,
+#![cfg_attr(not(feature = "std"), no_std)]
+
+#[cfg(all(not(feature = "std"), not(feature = "libm")))]
+compile_error!("arete_public requires either the `std` or the `libm` feature");
+
+use core::{
+    ffi::CStr,
+    fmt,
+    ops::{Add, Mul, Sub},
 };
 
 use game_module_macro::{Component, Resource};
-use nalgebra_glm::Vec2;
+use nalgebra::ComplexField;
+use nalgebra_glm::{self as glm, Vec2};
 
+#[cfg(feature = "std")]
+pub use engine::*;
+#[cfg(feature = "std")]
+pub use events::*;
 pub use linalg::*;
-
+#[cfg(feature = "test-util")]
+pub use mock::*;
+#[cfg(feature = "std")]
+pub use query::*;
+#[cfg(feature = "std")]
+pub use removed::*;
+#[cfg(feature = "std")]
+pub use system_info::*;
+
+#[cfg(feature = "std")]
+mod engine;
+#[cfg(feature = "std")]
+mod events;
 mod linalg;
+#[cfg(feature = "test-util")]
+mod mock;
+#[cfg(feature = "std")]
+mod query;
+#[cfg(feature = "std")]
+mod removed;
+#[cfg(feature = "std")]
+mod system_info;
 
 /// The version of Arete which this module is designed to support.
 pub const ENGINE_VERSION: u32 = make_api_version(0, 1, 0);
@@ -41,6 +89,31 @@ pub const fn api_version_compatible(version: u32) -> bool {
         && api_version_minor(ENGINE_VERSION) == api_version_minor(version)
 }
 
+/// Hashes a component layout, as returned by the generated `component_layout_hash` function.
+///
+/// `words` are the component's size and alignment, followed by the offset and `FieldKind` of each
+/// reflected field, in declaration order. The hash is 64-bit FNV-1a over the little-endian bytes
+/// of each word, so the engine can compute the same hash from its own view of the layout.
+pub const fn layout_hash(words: &[u64]) -> u64 {
+    let mut hash = 0xcbf2_9ce4_8422_2325;
+    let mut i = 0;
+
+    while i < words.len() {
+        let bytes = words[i].to_le_bytes();
+        let mut j = 0;
+
+        while j < bytes.len() {
+            hash ^= bytes[j] as u64;
+            hash = hash.wrapping_mul(0x0100_0000_01b3);
+            j += 1;
+        }
+
+        i += 1;
+    }
+
+    hash
+}
+
 /// A handle identifying a component or resource type.
 pub type ComponentId = u16;
 
@@ -51,12 +124,36 @@ pub struct AssetId(pub u32);
 
 /// A trait representing an ECS Component. All structs which are to be used as
 /// a Component must `#[derive(Component)]`.
+///
+/// Components must also implement `Default`, which the engine uses as the initial value when
+/// adding the component to an entity in the editor. A component without a sensible default
+/// can opt out with `#[component(no_default)]`, in which case the editor can't add it.
+///
+/// A component may declare other components which every entity with it must also have, i.e.
+/// `#[component(requires(Transform))]`. The engine checks this when spawning an entity, logging
+/// any violation (or panicking, in debug builds).
 pub trait Component: Copy + Send + Sync + Sized {
     fn id() -> ComponentId;
 
     fn set_id(id: ComponentId);
 
     fn string_id() -> &'static CStr;
+
+    /// A small id which is the same for every build of the module with the same set of component
+    /// types, for compact network serialization. This is valid once the engine has set the
+    /// component's `id()`.
+    ///
+    /// Network ids are assigned in alphabetical order of the component type names, so adding or
+    /// removing a component type may shift the ids of others. Pin a component's network id with
+    /// `#[component(id = 7)]` to keep it stable as the module changes.
+    fn network_id() -> u16;
+
+    fn set_network_id(id: u16);
+
+    /// The `string_id` functions of the components which this component requires.
+    fn requires() -> &'static [fn() -> &'static CStr] {
+        &[]
+    }
 }
 
 /// A trait representing an ECS Resource. All structs which are to be used as
@@ -72,6 +169,11 @@ pub trait Resource: Send + Sync + Sized {
 }
 
 /// A handle representing an entity.
+///
+/// Queries may include `&EntityId` to get the id of each entity, i.e.
+/// `Query<(&Transform, &EntityId)>`. The engine provides the id directly, since it is not stored
+/// as component data: `EntityId` only implements `Component` so that it fits query templates,
+/// and its component id is never set. It cannot be queried mutably.
 #[repr(transparent)]
 #[derive(Component, PartialEq, Eq)]
 pub struct EntityId(pub u64);
@@ -145,12 +247,19 @@ pub struct Cursor {
     pub delta_position: ScreenPosition,
 }
 
+/// The state of a key or button. The engine writes this directly, so its layout must match the
+/// engine's.
 #[repr(C)]
 #[derive(Copy, Clone, Debug, Default)]
 pub struct ButtonState {
     pub pressed: bool,
     pub pressed_this_frame: bool,
     pub released_this_frame: bool,
+    /// The number of presses since last frame. Unlike `pressed_this_frame`, this counts every
+    /// press when the button is tapped several times within one (long) frame. Saturates at 255.
+    pub press_count: u8,
+    /// The number of releases since last frame (see `press_count`).
+    pub release_count: u8,
 }
 
 /// A unique touch identifier.
@@ -188,6 +297,77 @@ pub struct ScreenPosition {
     pub y: f32,
 }
 
+impl ScreenPosition {
+    pub const CENTER: Self = Self { x: 0.5, y: 0.5 };
+
+    pub const TOP_LEFT: Self = Self { x: 0.0, y: 0.0 };
+
+    pub const TOP_RIGHT: Self = Self { x: 1.0, y: 0.0 };
+
+    pub const BOTTOM_LEFT: Self = Self { x: 0.0, y: 1.0 };
+
+    pub const BOTTOM_RIGHT: Self = Self { x: 1.0, y: 1.0 };
+
+    /// Returns the distance to `other`, in screen units. Note that screen units are not square
+    /// unless the screen is, since both axes span `[0, 1]`.
+    pub fn distance(self, other: Self) -> f32 {
+        Vec2::from(self - other).norm()
+    }
+
+    /// Linearly interpolates between `self` (at `t = 0`) and `other` (at `t = 1`).
+    pub fn lerp(self, other: Self, t: f32) -> Self {
+        self + (other - self) * t
+    }
+}
+
+impl Add for ScreenPosition {
+    type Output = Self;
+
+    fn add(self, rhs: Self) -> Self::Output {
+        Self {
+            x: self.x + rhs.x,
+            y: self.y + rhs.y,
+        }
+    }
+}
+
+impl Sub for ScreenPosition {
+    type Output = Self;
+
+    fn sub(self, rhs: Self) -> Self::Output {
+        Self {
+            x: self.x - rhs.x,
+            y: self.y - rhs.y,
+        }
+    }
+}
+
+impl Mul<f32> for ScreenPosition {
+    type Output = Self;
+
+    fn mul(self, rhs: f32) -> Self::Output {
+        Self {
+            x: self.x * rhs,
+            y: self.y * rhs,
+        }
+    }
+}
+
+impl From<Vec2> for ScreenPosition {
+    fn from(value: Vec2) -> Self {
+        Self {
+            x: value.x,
+            y: value.y,
+        }
+    }
+}
+
+impl From<ScreenPosition> for Vec2 {
+    fn from(value: ScreenPosition) -> Self {
+        Vec2::new(value.x, value.y)
+    }
+}
+
 impl InputState {
     /// A helper function returning all active touches.
     pub fn touches(&self) -> impl Iterator<Item = &TouchInput> {
@@ -214,13 +394,61 @@ impl InputState {
             .iter()
             .filter(|touch| matches!(touch.phase, TouchPhase::Ended))
     }
+
+    /// A helper function treating the first active (not ended) touch as a virtual joystick.
+    ///
+    /// Returns the touch position relative to `center`, divided by `radius`, with its length
+    /// clamped to 1 (so each axis is in `[-1, 1]`, and a touch outside the radius points in the
+    /// same direction at full deflection). Like `ScreenPosition`, +x is right and +y is down.
+    ///
+    /// Returns `None` if there are no active touches.
+    pub fn virtual_stick(&self, center: ScreenPosition, radius: f32) -> Option<Vec2> {
+        self.touches()
+            .find(|touch| !matches!(touch.phase, TouchPhase::Ended))
+            .map(|touch| (Vec2::from(touch.position - center) / radius).cap_magnitude(1.0))
+    }
 }
 
 /// A resource containing values which are constant for the whole frame.
 #[repr(C)]
-#[derive(Resource, Default, Clone, Copy)]
+#[derive(Resource, Clone, Copy)]
 pub struct FrameConstants {
     pub delta_time: f32,
+    /// How far rendering is between the previous and current simulation steps, in the range
+    /// [0, 1]. The renderer uses this to interpolate entities with a `PreviousTransform`.
+    ///
+    /// When the simulation steps once per rendered frame, this is always 1, as is the default.
+    pub render_alpha: f32,
+}
+
+impl Default for FrameConstants {
+    fn default() -> Self {
+        Self {
+            delta_time: 0.0,
+            render_alpha: 1.0,
+        }
+    }
+}
+
+/// A resource with timing statistics for the previous frame, filled in by the engine, i.e. for
+/// a performance overlay or to diagnose stutter. Writes to it are overwritten each frame.
+#[repr(C)]
+#[derive(Resource, Debug, Default, Clone, Copy)]
+pub struct FrameStats {
+    /// The wall-clock time between the start of the previous frame and the start of this one,
+    /// in milliseconds.
+    pub frame_time_ms: f32,
+    /// The time the engine spent on the CPU during the previous frame, including running
+    /// systems and recording rendering commands, but not waiting for vsync, in milliseconds.
+    pub cpu_time_ms: f32,
+    /// The time the GPU spent rendering the previous frame, in milliseconds.
+    ///
+    /// This is always 0 on platforms without GPU timestamp queries (i.e. some mobile GPUs and the
+    /// web), and on the first frame.
+    pub gpu_time_ms: f32,
+    /// The number of frames which missed their presentation deadline since the engine started,
+    /// i.e. a frame took longer than one display refresh interval.
+    pub dropped_frames: u32,
 }
 
 /// A component representing a 3D transform.
@@ -242,6 +470,57 @@ impl Default for Transform {
     }
 }
 
+impl Transform {
+    /// Returns the direction this transform is facing, i.e. `Vec3::FORWARD` rotated by `rotation`.
+    pub fn forward(&self) -> Vec3 {
+        glm::quat_rotate_vec3(&self.rotation, &Vec3::FORWARD.into()).into()
+    }
+
+    /// Returns `Vec3::UP` rotated by `rotation`.
+    pub fn up(&self) -> Vec3 {
+        glm::quat_rotate_vec3(&self.rotation, &Vec3::UP.into()).into()
+    }
+
+    /// Returns `Vec3::RIGHT` rotated by `rotation`.
+    pub fn right(&self) -> Vec3 {
+        glm::quat_rotate_vec3(&self.rotation, &Vec3::RIGHT.into()).into()
+    }
+
+    /// Converts a point from this transform's local space to the parent space (i.e. world space
+    /// for an entity without a `Parent`), by applying `scale`, then `rotation`, then `position`.
+    pub fn transform_point(&self, local: Vec3) -> Vec3 {
+        self.position + self.transform_direction(local * self.scale)
+    }
+
+    /// Converts a direction from this transform's local space to the parent space, by applying
+    /// only `rotation`. Unlike `transform_point()`, this ignores `position` and `scale`, so the
+    /// length of `local` is preserved.
+    pub fn transform_direction(&self, local: Vec3) -> Vec3 {
+        glm::quat_rotate_vec3(&self.rotation, &local.into()).into()
+    }
+
+    /// Returns `true` if `position` and `scale` are finite. A non-finite transform makes the
+    /// renderer skip the entity, so this is a good first check when an entity vanishes.
+    pub fn is_finite(&self) -> bool {
+        self.position.is_finite() && self.scale.is_finite()
+    }
+}
+
+/// A component holding an entity's `Transform` as of the previous simulation step.
+///
+/// When the simulation runs at a lower rate than rendering, the renderer draws entities with a
+/// `PreviousTransform` at the interpolation between `PreviousTransform` and `Transform` by
+/// `FrameConstants::render_alpha`, instead of at `Transform`. This smooths out motion which would
+/// otherwise stutter. Rotation is interpolated with slerp, position and scale linearly.
+///
+/// The engine copies `Transform` into `PreviousTransform` at the start of each simulation step,
+/// before any systems run. When teleporting an entity, set both to the new transform to avoid
+/// interpolating across the jump.
+#[repr(transparent)]
+#[derive(Component, Debug, Default)]
+#[component(requires(Transform))]
+pub struct PreviousTransform(pub Transform);
+
 /// A component representing a 3D camera.
 #[repr(C)]
 #[derive(Component, Debug)]
@@ -251,6 +530,8 @@ pub struct Camera {
     /// Near clip plane. A larger value results in less z-fighting at larger
     /// distances, but cannot render objects closer than the near plane.
     pub near_plane: f32,
+    /// The render layers visible to this camera. See `RenderLayer`.
+    pub layer_mask: u32,
 }
 
 impl Default for Camera {
@@ -258,18 +539,117 @@ impl Default for Camera {
         Self {
             fov: 1.0,
             near_plane: 0.1,
+            layer_mask: u32::MAX,
         }
     }
 }
 
+/// Returns the `Transform` of a camera at `eye` looking at `target`, with `up` as the
+/// approximate up direction, i.e. for follow cameras. Its scale is 1.
+///
+/// `glm::quat_look_at()` returns the rotation of the view matrix, which takes world space into
+/// camera space: the camera's own rotation is its inverse.
+///
+/// NOTE: unlike other entities, a camera looks along its local -Z, so the returned transform's
+/// `forward()` points from `target` back to `eye`: the view direction is `-forward()`. Likewise,
+/// `right()` points to the left of the screen. `up()` is the screen's up direction.
+///
+/// `eye` and `target` must not coincide, and `up` must not be parallel to `target - eye`,
+/// otherwise the rotation is not finite.
+pub fn camera_look_at(eye: Vec3, target: Vec3, up: Vec3) -> Transform {
+    let rotation = glm::quat_inverse(&glm::quat_look_at(&(target - eye).into(), &up.into()));
+
+    Transform {
+        position: eye,
+        rotation: rotation.into(),
+        ..Default::default()
+    }
+}
+
+/// A component which makes a `Camera` render to an offscreen target created with
+/// `Engine::create_render_target()`, instead of the main window.
+///
+/// A camera without a `RenderTarget` draws to the main window.
+#[repr(C)]
+#[derive(Component, Debug, PartialEq, Eq)]
+#[component(requires(Camera), no_default)]
+pub struct RenderTarget(pub u32);
+
+/// A component which assigns an entity to one or more render layers, as a bitmask.
+///
+/// There are 32 layers, one per bit. A camera renders an entity only if the entity's layers and
+/// the camera's `layer_mask` intersect (i.e. `render_layer.0 & camera.layer_mask != 0`).
+///
+/// Entities without a `RenderLayer` are on every layer, as is the default `RenderLayer`.
+#[repr(C)]
+#[derive(Component, Debug, PartialEq, Eq)]
+pub struct RenderLayer(pub u32);
+
+impl Default for RenderLayer {
+    fn default() -> Self {
+        Self(u32::MAX)
+    }
+}
+
 /// A component representing a normalized RGB color.
 /// Values are in the range [0, 1], but values may exceed the upper bound.
+///
+/// Colors are stored in *linear* space, which the renderer lights and blends in. Color pickers
+/// and image editors use sRGB instead, so convert such colors with `Color::from_srgb()`.
 #[repr(C)]
 #[derive(Component, Debug)]
 pub struct Color {
     pub val: Vec3,
 }
 
+impl Color {
+    /// Creates a color from sRGB values, i.e. from a color picker.
+    pub fn from_srgb(srgb: Vec3) -> Self {
+        Self {
+            val: Vec3::new(
+                srgb_to_linear(srgb.x),
+                srgb_to_linear(srgb.y),
+                srgb_to_linear(srgb.z),
+            ),
+        }
+    }
+
+    /// Returns the sRGB values of this color.
+    pub fn to_srgb(self) -> Vec3 {
+        Vec3::new(
+            linear_to_srgb(self.val.x),
+            linear_to_srgb(self.val.y),
+            linear_to_srgb(self.val.z),
+        )
+    }
+
+    /// Linearly interpolates between `self` and `other` by `t`, in linear space (where colors are
+    /// stored). This blends like light does: the midpoint of black and white is 0.5 linear, which
+    /// is the lighter 0.735 in sRGB, rather than the darker sRGB 0.5 of an sRGB lerp.
+    pub fn lerp(self, other: Color, t: f32) -> Color {
+        Self {
+            val: self.val + (other.val - self.val) * t,
+        }
+    }
+}
+
+/// The sRGB transfer functions, per channel.
+fn srgb_to_linear(c: f32) -> f32 {
+    if c <= 0.04045 {
+        c / 12.92
+    } else {
+        ComplexField::powf((c + 0.055) / 1.055, 2.4)
+    }
+}
+
+fn linear_to_srgb(c: f32) -> f32 {
+    if c <= 0.0031308 {
+        c * 12.92
+    } else {
+        1.055 * ComplexField::powf(c, 1.0 / 2.4) - 0.055
+    }
+}
+
 impl Default for Color {
     fn default() -> Self {
         Self {
@@ -310,6 +690,19 @@ impl Default for GlobalLighting {
     }
 }
 
+/// A resource with the renderer's post-processing settings. Every effect is disabled at 0.
+#[repr(C)]
+#[derive(Resource, Debug, Default)]
+pub struct PostProcess {
+    /// How much the screen darkens towards its edges, in `[0, 1]`.
+    pub vignette: f32,
+    /// How far the color channels are offset from each other towards the screen edges, as a
+    /// fraction of the screen size.
+    pub chromatic_aberration: f32,
+    /// The intensity of the glow around bright areas.
+    pub bloom_intensity: f32,
+}
+
 /// A resource representing the game window size (in pixels).
 #[repr(C)]
 #[derive(Resource, Debug, Default)]
@@ -323,12 +716,20 @@ pub struct Aspect {
 #[derive(Resource, Debug)]
 pub struct FrameRateSettings {
     pub frame_rate_limit: f64,
+    /// The maximum `FrameConstants::delta_time`, in seconds. Longer frames (i.e. hitches, or
+    /// resuming from a breakpoint) report this instead of their real duration.
+    ///
+    /// Clamping keeps a single long frame from moving objects far enough to tunnel through
+    /// geometry, at the cost of the game running in slow motion during hitches. Set this to
+    /// `f64::INFINITY` to disable clamping.
+    pub max_delta_time: f64,
 }
 
 impl Default for FrameRateSettings {
     fn default() -> Self {
         Self {
             frame_rate_limit: f64::INFINITY,
+            max_delta_time: 0.1,
         }
     }
 }
@@ -336,270 +737,208 @@ impl Default for FrameRateSettings {
 /// A component representing a moveable static mesh.
 #[repr(C)]
 #[derive(Component, Debug)]
+#[component(requires(Transform), no_default)]
 pub struct DynamicStaticMesh {
     pub asset_id: AssetId,
 }
 
-/// A generic reference to a component. This type is necessary to pass components to `Engine::spawn()`.
+/// A component which despawns its entity after `remaining` seconds.
 ///
-/// It is NOT recommended to use this struct manually -- use the `bundle!()` macro to automatically convert components.
+/// The engine decrements `remaining` by `FrameConstants::delta_time` once per frame, after all
+/// systems have run, and despawns the entity (like `Engine::despawn()`, so the despawn is
+/// deferred to the end of the frame) once it reaches zero. Writing `remaining` from a system
+/// restarts the countdown from the written value.
 #[repr(C)]
-pub struct ComponentRef<'a> {
-    pub component_id: ComponentId,
-    pub component_size: usize,
-    pub component_val: *const c_void,
-    marker: PhantomData<&'a u8>,
-}
-
-impl<'a, C> From<&'a C> for ComponentRef<'a>
-where
-    C: Component,
-{
-    fn from(value: &'a C) -> Self {
-        Self {
-            component_id: C::id(),
-            component_size: size_of::<C>(),
-            component_val: value as *const C as *const c_void,
-            marker: PhantomData,
-        }
-    }
+#[derive(Component, Debug, Default)]
+pub struct Lifetime {
+    pub remaining: f32,
 }
 
-impl<'a, C> From<&'a mut C> for ComponentRef<'a>
-where
-    C: Component,
-{
-    fn from(value: &'a mut C) -> Self {
-        (&*value).into()
-    }
-}
+/// A marker component which allows the renderer to draw entities as instances of a shared mesh,
+/// batching them into a single draw.
+///
+/// Instanced entities are only batched together if their `DynamicStaticMesh` components have the
+/// exact same `AssetId`. Per-instance color comes from each entity's `Color` component.
+#[repr(C)]
+#[derive(Component, Debug, Default)]
+pub struct Instanced;
 
-/// Converts a list of component references into the type which `Engine::spawn()` accepts.
-#[macro_export]
-macro_rules! bundle {
-    ($($c:expr),* $(,)?) => (
-        &[$(::arete_public::ComponentRef::from($c)),*]
-    )
-}
+/// A marker component which makes an entity inactive without despawning it, i.e. for pooled
+/// objects. Adding or removing `Disabled` is much cheaper than despawning and respawning.
+///
+/// The engine excludes disabled entities from every query, unless the query template includes
+/// `With<Disabled>`, in which case the query matches only disabled entities (i.e. to re-enable
+/// them). The renderer also skips disabled entities. Tools which need to visit every entity can
+/// use `Query::include_disabled()`.
+#[repr(C)]
+#[derive(Component, Debug, Default)]
+pub struct Disabled;
 
-/// A resource which exposes engine functionality, like spawning and despawning.
+/// A component which gives an entity a name, i.e. for debugging, or for scripts to find it with
+/// `Engine::find_by_name()`.
+///
+/// The name is stored inline as a nul-terminated string of at most `Name::MAX_LEN` bytes, so
+/// that it can be a plain `Copy` component.
 #[repr(C)]
-#[derive(Resource)]
-pub struct Engine {
-    engine_handle: *const c_void,
-    spawn: unsafe extern "C" fn(*const c_void, *const ComponentRef, usize) -> EntityId,
-    despawn: unsafe extern "C" fn(*const c_void, EntityId),
-    set_component_value: unsafe extern "C" fn(*const c_void, *const c_void, *const ComponentRef),
-    load_asset: unsafe extern "C" fn(*const c_void, *const c_char) -> AssetId,
+#[derive(Component, Default, PartialEq, Eq)]
+pub struct Name {
+    bytes: [u8; Name::MAX_LEN + 1],
 }
 
-impl Default for Engine {
-    fn default() -> Self {
-        unreachable!("cannot construct Engine resource")
-    }
-}
+impl Name {
+    /// The maximum length of a name in bytes, excluding the nul terminator.
+    pub const MAX_LEN: usize = 31;
 
-unsafe impl Send for Engine {}
-unsafe impl Sync for Engine {}
+    /// Creates a name, truncating it to `Name::MAX_LEN` bytes. Truncation may split a multi-byte
+    /// UTF-8 character.
+    pub fn new(name: &CStr) -> Self {
+        let name = name.to_bytes();
+        let len = name.len().min(Self::MAX_LEN);
 
-impl Engine {
-    /// Spawns an entity with the specified components.
-    ///
-    /// Returns the `EntityId` of the new entity.
-    ///
-    /// NOTE: spawns are deferred until the end of the frame, so the spawned entity will
-    /// not be iterated by queries on the frame it is spawned.
-    pub fn spawn(&self, components: &[ComponentRef]) -> EntityId {
-        unsafe { (self.spawn)(self.engine_handle, components.as_ptr(), components.len()) }
+        let mut bytes = [0; Self::MAX_LEN + 1];
+        bytes[..len].copy_from_slice(&name[..len]);
+
+        Self { bytes }
     }
 
-    /// Despawns an entity with the specified `EntityId`.
-    ///
-    /// NOTE: despawns are deferred until the end of the frame, so the despawned entity will
-    /// still be iterated by queries on the frame it is despawned.
-    pub fn despawn(&self, entity_id: EntityId) {
-        unsafe {
-            (self.despawn)(self.engine_handle, entity_id);
-        }
+    pub fn as_c_str(&self) -> &CStr {
+        // `new` always leaves a nul terminator in the last byte
+        CStr::from_bytes_until_nul(&self.bytes).unwrap_or_default()
     }
+}
 
-    /// Loads a static mesh asset. It is safe to call this for the same asset multiple times.
-    ///
-    /// Returns the identifying `AssetId` of the newly-loaded asset. If the asset has already been
-    /// loaded, it will return the `AssetId` of the originally-loaded asset.
-    pub fn load_asset(&self, asset_path: &CStr) -> AssetId {
-        unsafe { (self.load_asset)(self.engine_handle, asset_path.as_ptr()) }
+impl fmt::Debug for Name {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("Name").field(&self.as_c_str()).finish()
     }
 }
 
-/// A query is essentially an iterator over a number of entities, based on the specified
-/// template components. For example, a query of type `Query<&Transform>` will iterate over
-/// all the entities with a Transform component, and provide access to their `Transform` component.
+/// A component which attaches an entity to a parent entity, forming a hierarchy. The entity's
+/// `Transform` is relative to its parent's.
 ///
-/// Generic `Q` specifies the components to include in this query. Components *must* be references.
-/// If the query specifies more than one component, `Q` should be a tuple (i.e. `Query<(&A, &B)>`).
+/// `Engine::despawn_recursive()` follows this relation to despawn an entity's descendants, and
+/// `Engine::detach_children()` to break the hierarchy up without moving the children.
 #[repr(C)]
-pub struct Query<Q> {
-    query_handle: *mut c_void,
-    marker: PhantomData<Q>,
+#[derive(Component, PartialEq, Eq)]
+#[component(no_default)]
+pub struct Parent {
+    pub entity_id: EntityId,
 }
 
-unsafe impl<Q> Send for Query<Q> {}
-unsafe impl<Q> Sync for Query<Q> {}
+#[cfg(test)]
+mod tests {
+    use std::sync::{Mutex, MutexGuard, PoisonError};
 
-impl<Q> Query<Q> {
-    pub fn new(query_handle: *mut c_void) -> Self {
-        Self {
-            query_handle,
-            marker: PhantomData,
-        }
-    }
+    use super::*;
 
-    /// Returns a reference to the requested component for an entity in this query.
-    ///
-    /// `entity_id` is the entity to get the component of. `T` must be one of the components in `Q`.
-    ///
-    /// Returns `None` if the lookup failed (i.e. the entity does not exist in this query).
-    pub fn get<T: Component>(&self, entity_id: EntityId) -> Option<&T> {
-        unsafe {
-            let ptr =
-                _QUERY_GET_FN.unwrap_unchecked()(self.query_handle, entity_id, T::id()) as *const T;
-            ptr.as_ref()
-        }
+    /// Serializes the tests which write process-global state (component ids and query
+    /// callbacks), since tests run on parallel threads.
+    pub(crate) fn lock_globals() -> MutexGuard<'static, ()> {
+        static GLOBALS: Mutex<()> = Mutex::new(());
+
+        GLOBALS.lock().unwrap_or_else(PoisonError::into_inner)
     }
 
-    /// Returns a mutable reference to the requested component for an entity in this query.
-    ///
-    /// `entity_id` is the entity to get the component of. `T` must be one of the components in `Q`.
-    ///
-    /// Returns `None` if the lookup failed (i.e. the entity does not exist in this query).
-    pub fn get_mut<T: Component>(&mut self, entity_id: EntityId) -> Option<&mut T> {
-        unsafe {
-            let ptr = _QUERY_GET_MUT_FN.unwrap_unchecked()(self.query_handle, entity_id, T::id())
-                as *mut T;
-            ptr.as_mut()
-        }
+    fn assert_near(actual: Vec3, expected: Vec3) {
+        assert!(
+            (actual - expected).norm() < 1e-5,
+            "expected {expected}, got {actual}",
+        );
     }
 
-    /// Returns a reference to the requested component for the first entity in query. This can be
-    /// useful when the query covers only a single entity.
-    ///
-    /// Returns `None` if the lookup failed (i.e. the query does not cover any entities).
-    pub fn get_first<T: Component>(&self) -> Option<&T> {
-        unsafe {
-            let ptr =
-                _QUERY_GET_FIRST_FN.unwrap_unchecked()(self.query_handle, T::id()) as *const T;
-            ptr.as_ref()
-        }
+    #[test]
+    fn transform_point_and_direction() {
+        let transform = Transform {
+            position: Vec3::new(1.0, 2.0, 3.0),
+            rotation: glm::quat_angle_axis(core::f32::consts::FRAC_PI_2, &glm::Vec3::y()).into(),
+            scale: Vec3::new(2.0, 3.0, 4.0),
+        };
+
+        // scaled to (2, 3, 4), rotated to (4, 3, -2), then translated
+        assert_near(
+            transform.transform_point(Vec3::new(1.0, 1.0, 1.0)),
+            Vec3::new(5.0, 5.0, 1.0),
+        );
+
+        // rotation only: neither translated nor scaled
+        assert_near(
+            transform.transform_direction(Vec3::new(1.0, 1.0, 1.0)),
+            Vec3::new(1.0, 1.0, -1.0),
+        );
+        assert_near(
+            transform.transform_direction(Vec3::default()),
+            Vec3::default(),
+        );
     }
 
-    /// Returns a mutable reference to the requested component for the first entity in query. This
-    /// can be useful when the query covers only a single entity.
-    ///
-    /// Returns `None` if the lookup failed (i.e. the query does not cover any entities).
-    pub fn get_first_mut<T: Component>(&mut self) -> Option<&mut T> {
-        unsafe {
-            let ptr =
-                _QUERY_GET_FIRST_MUT_FN.unwrap_unchecked()(self.query_handle, T::id()) as *mut T;
-            ptr.as_mut()
-        }
+    #[test]
+    fn camera_look_at_faces_target() {
+        let eye = Vec3::new(1.0, 5.0, -10.0);
+        let target = Vec3::new(-3.0, 1.0, 2.0);
+
+        let transform = camera_look_at(eye, target, Vec3::UP);
+
+        // The camera looks along -forward() (see `camera_look_at`).
+        assert_near(-transform.forward(), (target - eye).normalize());
+        assert_near(transform.position, eye);
+        assert!(transform.up().y > 0.0);
     }
 
-    /// Iterates over all entities in this query by calling the provided function once per entity.
-    ///
-    /// This function only runs on a single thread. Prefer `par_for_each` where possible
-    /// (see `par_for_each` docs for details).
-    ///
-    /// The parameters of the function *must* match the order and mutability of the query template.
-    pub fn for_each<F>(&mut self, mut f: F)
-    where
-        F: FnMut(Q),
-    {
-        unsafe extern "C" fn callback<Q, F: FnMut(Q)>(
-            entity_data: *mut *mut c_void,
-            user_data: *mut c_void,
-        ) -> c_int {
-            match catch_unwind(|| {
-                let f = &mut *(user_data as *mut F);
-                f(transmute_copy(&*(entity_data as *mut Q)));
-            }) {
-                Ok(..) => 0,
-                Err(..) => 1,
-            }
-        }
+    #[test]
+    fn color_srgb_midpoint() {
+        let grey = Color::from_srgb(Vec3::new(0.5, 0.5, 0.5));
+        assert!((grey.val.x - 0.214).abs() < 1e-3);
+        assert_near(grey.to_srgb(), Vec3::new(0.5, 0.5, 0.5));
 
-        unsafe {
-            _QUERY_FOR_EACH_FN.unwrap_unchecked()(
-                self.query_handle,
-                callback::<Q, F>,
-                &mut f as *mut _ as _,
-            );
-        }
+        let black = Color::from_srgb(Vec3::new(0.0, 0.0, 0.0));
+        let white = Color::from_srgb(Vec3::new(1.0, 1.0, 1.0));
+
+        // Halfway in linear space is much brighter than halfway in sRGB.
+        let midpoint = black.lerp(white, 0.5);
+        assert_near(midpoint.val, Vec3::new(0.5, 0.5, 0.5));
+        assert!((midpoint.to_srgb().x - 0.735).abs() < 1e-3);
     }
 
-    /// Iterates over all entities in this query by calling the provided function once per entity.
-    ///
-    /// This version of for-each will be run in parallel and can provide significant performance improvements.
-    /// This version should be the default, unless it is necessary to mutate captured state.
-    ///
-    /// The parameters of the function *must* match the order and mutability of the query template.
-    pub fn par_for_each<F>(&mut self, f: F)
-    where
-        F: Fn(Q) + Send + Sync,
-    {
-        unsafe extern "C" fn callback<Q, F: Fn(Q)>(
-            entity_data: *mut *mut c_void,
-            user_data: *const c_void,
-        ) -> c_int {
-            match catch_unwind(|| {
-                let f = &*(user_data as *const F);
-                f(transmute_copy(&*(entity_data as *mut Q)));
-            }) {
-                Ok(..) => 0,
-                Err(..) => 1,
-            }
-        }
+    fn input_with_touches(touches: &[(TouchPhase, ScreenPosition)]) -> InputState {
+        let mut input = InputState::default();
 
-        unsafe {
-            _QUERY_PAR_FOR_EACH_FN.unwrap_unchecked()(
-                self.query_handle,
-                callback::<Q, F>,
-                &f as *const _ as _,
-            );
+        for (i, (phase, position)) in touches.iter().enumerate() {
+            input.touches[i] = TouchInput {
+                phase: *phase,
+                position: *position,
+                ..Default::default()
+            };
         }
-    }
-}
 
-// global callback functions
+        input.touches_len = touches.len();
+        input
+    }
 
-pub static mut _QUERY_GET_FN: Option<
-    unsafe extern "C" fn(*const c_void, EntityId, ComponentId) -> *const c_void,
-> = None;
+    #[test]
+    fn virtual_stick() {
+        let center = ScreenPosition { x: 0.25, y: 0.75 };
 
-pub static mut _QUERY_GET_MUT_FN: Option<
-    unsafe extern "C" fn(*mut c_void, EntityId, ComponentId) -> *mut c_void,
-> = None;
+        let stick = |touches: &[(TouchPhase, ScreenPosition)]| {
+            input_with_touches(touches).virtual_stick(center, 0.2)
+        };
 
-pub static mut _QUERY_GET_FIRST_FN: Option<
-    unsafe extern "C" fn(*const c_void, ComponentId) -> *const c_void,
-> = None;
+        // no touch, or only ended touches
+        assert_eq!(stick(&[]), None);
+        assert_eq!(stick(&[(TouchPhase::Ended, center)]), None);
 
-pub static mut _QUERY_GET_FIRST_MUT_FN: Option<
-    unsafe extern "C" fn(*mut c_void, ComponentId) -> *mut c_void,
-> = None;
+        // exactly at the center
+        assert_eq!(stick(&[(TouchPhase::Began, center)]), Some(Vec2::zeros()));
 
-pub static mut _QUERY_FOR_EACH_FN: Option<
-    unsafe extern "C" fn(
-        *mut c_void,
-        unsafe extern "C" fn(*mut *mut c_void, *mut c_void) -> c_int,
-        *mut c_void,
-    ),
-> = None;
+        // inside the radius, skipping the ended touch
+        let inside = stick(&[
+            (TouchPhase::Ended, ScreenPosition { x: 0.0, y: 0.0 }),
+            (TouchPhase::Moved, ScreenPosition { x: 0.35, y: 0.7 }),
+        ])
+        .unwrap();
+        assert!((inside - Vec2::new(0.5, -0.25)).norm() < 1e-5);
 
-pub static mut _QUERY_PAR_FOR_EACH_FN: Option<
-    unsafe extern "C" fn(
-        *mut c_void,
-        unsafe extern "C" fn(*mut *mut c_void, *const c_void) -> c_int,
-        *const c_void,
-    ),
-> = None;
+        // outside the radius, clamped to unit length in the same direction
+        let outside = stick(&[(TouchPhase::Moved, ScreenPosition { x: 0.85, y: 1.55 })]).unwrap();
+        assert!((outside - Vec2::new(0.6, 0.8)).norm() < 1e-5);
+    }
+}
@@ -1,8 +1,10 @@
-use std::{
+use core::{
+    fmt,
     mem::transmute,
     ops::{Add, AddAssign, Deref, DerefMut, Div, DivAssign, Mul, MulAssign, Neg, Sub, SubAssign},
 };
 
+use nalgebra::{ComplexField, RealField, UnitQuaternion};
 use nalgebra_glm as glm;
 
 #[repr(C, align(16))]
@@ -15,7 +17,19 @@ pub struct Vec3 {
 }
 
 impl Vec3 {
-    pub fn new(x: f32, y: f32, z: f32) -> Self {
+    /// The forward direction of an unrotated object.
+    ///
+    /// Arete uses a right-handed coordinate system where +Y is up and +Z is forward,
+    /// which means +X points to the *left*.
+    pub const FORWARD: Self = Self::new(0.0, 0.0, 1.0);
+
+    /// The up direction of an unrotated object.
+    pub const UP: Self = Self::new(0.0, 1.0, 0.0);
+
+    /// The right direction of an unrotated object. Note that this is -X (see `Vec3::FORWARD`).
+    pub const RIGHT: Self = Self::new(-1.0, 0.0, 0.0);
+
+    pub const fn new(x: f32, y: f32, z: f32) -> Self {
         Self {
             x,
             y,
@@ -82,6 +96,123 @@ impl Vec3 {
     pub fn to_homogeneous(self) -> glm::Vec4 {
         glm::Vec4::new(self.x, self.y, self.z, 0.0)
     }
+
+    /// Moves towards `target` by at most `max_delta`. A negative `max_delta` is treated as 0, so
+    /// this never moves away from `target`.
+    ///
+    /// Returns `target` once it is within `max_delta`, so repeated calls arrive exactly at
+    /// `target` rather than overshooting it.
+    pub fn move_towards(self, target: Self, max_delta: f32) -> Self {
+        let max_delta = max_delta.max(0.0);
+        let delta = target - self;
+        let distance = delta.norm();
+
+        if distance <= max_delta {
+            target
+        } else {
+            self + delta * (max_delta / distance)
+        }
+    }
+
+    /// Returns `true` if every lane is finite (neither infinite nor NaN).
+    pub fn is_finite(self) -> bool {
+        self.x.is_finite() && self.y.is_finite() && self.z.is_finite()
+    }
+
+    /// Returns `true` if any lane is NaN, i.e. after normalizing a zero-length vector.
+    pub fn is_nan(self) -> bool {
+        self.x.is_nan() || self.y.is_nan() || self.z.is_nan()
+    }
+
+    /// Returns `self + a * b`, computing each lane as a fused multiply-add, which rounds once
+    /// rather than twice.
+    ///
+    /// This does not make integration loops (i.e. `position = position.fma(velocity, dt)` every
+    /// frame) measurably more accurate: the rounding error of the accumulation dominates, and
+    /// accumulates the same way as with `position + velocity * dt`.
+    ///
+    /// On targets without a hardware FMA instruction, this is slower than the naive version.
+    pub fn fma(self, a: Self, b: f32) -> Self {
+        Self::new(
+            ComplexField::mul_add(a.x, b, self.x),
+            ComplexField::mul_add(a.y, b, self.y),
+            ComplexField::mul_add(a.z, b, self.z),
+        )
+    }
+
+    /// Builds a unit direction from spherical angles, in radians.
+    ///
+    /// `yaw` rotates around +Y, starting at `Vec3::FORWARD`, matching a rotation of `yaw` radians
+    /// around +Y (i.e. `glm::quat_angle_axis(yaw, &glm::Vec3::y())`). `pitch` is the elevation
+    /// above the horizon, so `pi / 2` points straight up and `-pi / 2` straight down.
+    pub fn from_spherical(yaw: f32, pitch: f32) -> Self {
+        let (sin_yaw, cos_yaw) = ComplexField::sin_cos(yaw);
+        let (sin_pitch, cos_pitch) = ComplexField::sin_cos(pitch);
+
+        Self::new(sin_yaw * cos_pitch, sin_pitch, cos_yaw * cos_pitch)
+    }
+
+    /// Returns the `(yaw, pitch)` of this direction, with the convention of
+    /// `Vec3::from_spherical()`. The vector does not need to be normalized.
+    ///
+    /// Yaw is in `[-pi, pi]` and pitch in `[-pi / 2, pi / 2]`. At the poles (straight up or
+    /// down) the yaw is undefined, and 0 is returned for it.
+    pub fn to_spherical(self) -> (f32, f32) {
+        let horizontal = ComplexField::sqrt(self.x * self.x + self.z * self.z);
+
+        let yaw = if horizontal > 0.0 {
+            RealField::atan2(self.x, self.z)
+        } else {
+            0.0
+        };
+
+        (yaw, RealField::atan2(self.y, horizontal))
+    }
+
+    /// Returns whether each lane of `self` is less than the same lane of `rhs`.
+    pub fn cmplt(self, rhs: Self) -> [bool; 3] {
+        [self.x < rhs.x, self.y < rhs.y, self.z < rhs.z]
+    }
+
+    /// Returns whether each lane of `self` is greater than the same lane of `rhs`.
+    pub fn cmpgt(self, rhs: Self) -> [bool; 3] {
+        [self.x > rhs.x, self.y > rhs.y, self.z > rhs.z]
+    }
+
+    /// Returns whether each lane of `self` is less than or equal to the same lane of `rhs`.
+    pub fn cmple(self, rhs: Self) -> [bool; 3] {
+        [self.x <= rhs.x, self.y <= rhs.y, self.z <= rhs.z]
+    }
+
+    /// Returns whether each lane of `self` is greater than or equal to the same lane of `rhs`.
+    pub fn cmpge(self, rhs: Self) -> [bool; 3] {
+        [self.x >= rhs.x, self.y >= rhs.y, self.z >= rhs.z]
+    }
+}
+
+/// Reducers for the per-lane masks returned by `Vec3::cmplt()` and friends, i.e. checking
+/// whether a point is inside a box: `point.cmpge(min).all() && point.cmple(max).all()`.
+pub trait BoolMask {
+    fn all(self) -> bool;
+
+    fn any(self) -> bool;
+}
+
+impl BoolMask for [bool; 3] {
+    fn all(self) -> bool {
+        self[0] & self[1] & self[2]
+    }
+
+    fn any(self) -> bool {
+        self[0] | self[1] | self[2]
+    }
+}
+
+/// Formats as `(x, y, z)`. Formatting options (i.e. precision) apply to each component.
+impl fmt::Display for Vec3 {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt_components(f, &[self.x, self.y, self.z])
+    }
 }
 
 impl From<glm::Vec3> for Vec3 {
@@ -251,6 +382,35 @@ impl Default for Quat {
     }
 }
 
+impl Quat {
+    /// Rotates towards `target` by at most `max_radians`, along the shortest path. A negative
+    /// `max_radians` is treated as 0, so this never rotates away from `target`.
+    ///
+    /// Returns `target` once it is within `max_radians`, so repeated calls arrive exactly at
+    /// `target` rather than overshooting it. Both quaternions must be normalized.
+    pub fn rotate_towards(self, target: Self, max_radians: f32) -> Self {
+        let max_radians = max_radians.max(0.0);
+        let from = UnitQuaternion::new_unchecked(self.0);
+        let to = UnitQuaternion::new_unchecked(target.0);
+
+        let angle = from.angle_to(&to);
+
+        if angle <= max_radians {
+            return target;
+        }
+
+        from.try_slerp(&to, max_radians / angle, f32::EPSILON)
+            .map_or(target, |val| Self(val.into_inner()))
+    }
+}
+
+/// Formats as `(x, y, z, w)`. Formatting options (i.e. precision) apply to each component.
+impl fmt::Display for Quat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt_components(f, &[self.0.i, self.0.j, self.0.k, self.0.w])
+    }
+}
+
 impl From<glm::Quat> for Quat {
     fn from(value: glm::Quat) -> Self {
         Self(value)
@@ -270,3 +430,153 @@ impl DerefMut for Quat {
         &mut self.0
     }
 }
+
+fn fmt_components(f: &mut fmt::Formatter<'_>, components: &[f32]) -> fmt::Result {
+    f.write_str("(")?;
+
+    for (i, component) in components.iter().enumerate() {
+        if i > 0 {
+            f.write_str(", ")?;
+        }
+
+        fmt::Display::fmt(component, f)?;
+    }
+
+    f.write_str(")")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_exact(actual: Vec3, expected: Vec3) {
+        assert_eq!(
+            [actual.x, actual.y, actual.z],
+            [expected.x, expected.y, expected.z]
+        );
+    }
+
+    #[test]
+    fn fma_rounds_once() {
+        // (1 + 2^-12)^2 = 1 + 2^-11 + 2^-24, whose last term is lost when the product is rounded
+        // to f32 on its own.
+        let a = 1.0 + 2f32.powi(-12);
+        let base = Vec3::new(-(1.0 + 2f32.powi(-11)), 0.0, 1.0);
+
+        assert_exact(
+            base.fma(Vec3::new(a, 0.0, 0.0), a),
+            Vec3::new(2f32.powi(-24), 0.0, 1.0),
+        );
+        assert_exact(base + Vec3::new(a, 0.0, 0.0) * a, Vec3::new(0.0, 0.0, 1.0));
+    }
+
+    #[test]
+    fn spherical_round_trip() {
+        use core::f32::consts::{FRAC_PI_2, PI};
+
+        for yaw_step in -7..=7 {
+            for pitch in [
+                -FRAC_PI_2 + 1e-3,
+                -1.0,
+                -0.3,
+                0.0,
+                0.3,
+                1.0,
+                FRAC_PI_2 - 1e-3,
+            ] {
+                let yaw = yaw_step as f32 * PI / 8.0;
+
+                let direction = Vec3::from_spherical(yaw, pitch);
+                assert!((direction.norm() - 1.0).abs() < 1e-6);
+
+                let (yaw2, pitch2) = (direction * 3.0).to_spherical();
+                assert!((yaw2 - yaw).abs() < 1e-3, "yaw {yaw} became {yaw2}");
+                assert!(
+                    (pitch2 - pitch).abs() < 1e-5,
+                    "pitch {pitch} became {pitch2}"
+                );
+            }
+        }
+
+        // Straight up or down, the yaw is lost but the pitch is not.
+        let up = Vec3::from_spherical(1.0, FRAC_PI_2);
+        assert!((up - Vec3::UP).norm() < 1e-6);
+        assert!((up.to_spherical().1 - FRAC_PI_2).abs() < 1e-6);
+        assert_eq!(Vec3::new(0.0, -2.0, 0.0).to_spherical(), (0.0, -FRAC_PI_2));
+    }
+
+    #[test]
+    fn spherical_matches_transform_forward() {
+        for yaw in [-2.5, -1.0, 0.0, 0.7, 3.0] {
+            let transform = crate::Transform {
+                rotation: glm::quat_angle_axis(yaw, &glm::Vec3::y()).into(),
+                ..Default::default()
+            };
+
+            assert!((Vec3::from_spherical(yaw, 0.0) - transform.forward()).norm() < 1e-6);
+        }
+    }
+
+    #[test]
+    fn move_towards_snaps() {
+        let start = Vec3::default();
+        let target = Vec3::new(3.0, 4.0, 0.0);
+
+        // partial step
+        let moved = start.move_towards(target, 1.0);
+        assert!((moved - Vec3::new(0.6, 0.8, 0.0)).norm() < 1e-6);
+
+        // exactly the distance, and overshooting it
+        assert_exact(start.move_towards(target, 5.0), target);
+        assert_exact(start.move_towards(target, 7.0), target);
+
+        // zero distance, with and without a step
+        assert_exact(target.move_towards(target, 0.0), target);
+        assert_exact(target.move_towards(target, 1.0), target);
+
+        // negative steps do not move away
+        assert_exact(start.move_towards(target, -1.0), start);
+    }
+
+    #[test]
+    fn rotate_towards_snaps() {
+        let angle = |a: Quat, b: Quat| {
+            UnitQuaternion::new_unchecked(*a).angle_to(&UnitQuaternion::new_unchecked(*b))
+        };
+
+        let start = Quat::default();
+        let target: Quat = glm::quat_angle_axis(1.2, &glm::Vec3::y()).into();
+        let distance = angle(start, target);
+
+        // within `max_radians`, including exactly at it
+        assert_eq!(*start.rotate_towards(target, distance), *target);
+        assert_eq!(*start.rotate_towards(target, 2.0), *target);
+
+        // just outside `max_radians`, rotating by exactly `max_radians`
+        let max_radians = distance - 1e-3;
+        let rotated = start.rotate_towards(target, max_radians);
+        assert!((angle(start, rotated) - max_radians).abs() < 1e-5);
+        assert!((angle(rotated, target) - 1e-3).abs() < 1e-5);
+
+        // negative steps do not rotate away
+        assert!(angle(start, start.rotate_towards(target, -1.0)) < 1e-5);
+    }
+
+    #[test]
+    fn aabb_contains_point() {
+        let min = Vec3::new(-1.0, 0.0, -2.0);
+        let max = Vec3::new(1.0, 3.0, 2.0);
+
+        let contains = |point: Vec3| point.cmpge(min).all() && point.cmple(max).all();
+
+        assert!(contains(Vec3::new(0.0, 1.0, 0.0)));
+        assert!(contains(min));
+        assert!(contains(max));
+        assert!(!contains(Vec3::new(0.0, 3.5, 0.0)));
+        assert!(!contains(Vec3::new(-1.5, 1.0, 0.0)));
+
+        // strict comparisons exclude the faces
+        assert!(!(min.cmpgt(min).any() || min.cmplt(min).any()));
+        assert!(Vec3::new(0.0, 1.0, 0.0).cmplt(max).all());
+    }
+}
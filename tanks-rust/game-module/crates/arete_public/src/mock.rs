@@ -0,0 +1,132 @@
+use std::ffi::c_void;
+
+use crate::{Component, EntityId, QueryAccess, QueryData};
+
+/// An in-memory stand-in for `Query`, for unit testing gameplay logic without the engine.
+///
+/// A `MockQuery<Q>` holds its entities as `(EntityId, Q::Owned)` pairs, i.e. a
+/// `MockQuery<(&Transform, &mut Velocity)>` holds `(EntityId, (Transform, Velocity))`, and
+/// provides the same lookup and iteration functions as `Query` through `QueryAccess`.
+/// `par_for_each` runs on the calling thread.
+///
+/// The intended pattern is to keep the system a thin wrapper, and move its logic into a
+/// function generic over `QueryAccess`. Each reference in the template needs its own lifetime
+/// parameter, since the system's query has a distinct lifetime for each:
+///
+/// ```ignore
+/// #[system]
+/// fn velocity_update(mut query: Query<(&mut Transform, &Velocity)>, frame_constants: &FrameConstants) {
+///     apply_velocity(&mut query, frame_constants.delta_time);
+/// }
+///
+/// fn apply_velocity<'a, 'b>(
+///     query: &mut impl QueryAccess<Data = (&'a mut Transform, &'b Velocity)>,
+///     dt: f32,
+/// ) {
+///     query.par_for_each(|(transform, velocity)| transform.position += velocity.val * dt);
+/// }
+///
+/// #[test]
+/// fn velocity_moves_entity() {
+///     let mut query = MockQuery::new();
+///     query.push(EntityId(1), (Transform::default(), Velocity { val: Vec3::x() }));
+///
+///     apply_velocity(&mut query, 0.5);
+///
+///     assert_eq!(query.get::<Transform>(EntityId(1)).unwrap().position.x, 0.5);
+/// }
+/// ```
+///
+/// Component lookups match components by `Component::string_id()`, since component ids are
+/// only assigned by the engine. Functions which need the engine (i.e. `Engine::spawn()`) are
+/// still unavailable in tests.
+pub struct MockQuery<Q: QueryData> {
+    entities: Vec<(EntityId, Q::Owned)>,
+}
+
+impl<Q: QueryData> Default for MockQuery<Q> {
+    fn default() -> Self {
+        Self {
+            entities: Vec::new(),
+        }
+    }
+}
+
+impl<Q: QueryData> MockQuery<Q> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds an entity with the specified components, in template order.
+    pub fn push(&mut self, entity_id: EntityId, components: Q::Owned) {
+        self.entities.push((entity_id, components));
+    }
+
+    /// Returns the entities in this query, in iteration order.
+    pub fn entities(&self) -> &[(EntityId, Q::Owned)] {
+        &self.entities
+    }
+
+    /// Returns a pointer to component `T` of `owned`, or `None` if `T` is not in `Q`.
+    fn component<T: Component>(owned: *mut Q::Owned) -> Option<*mut T> {
+        unsafe { Q::component_ptrs(owned) }
+            .into_iter()
+            .find(|(string_id, _)| *string_id == T::string_id())
+            .map(|(_, ptr)| ptr as *mut T)
+    }
+
+    fn get_ptr<T: Component>(&self, entity_id: EntityId) -> Option<*mut T> {
+        let (_, owned) = self.entities.iter().find(|(id, _)| *id == entity_id)?;
+        Self::component(owned as *const Q::Owned as *mut Q::Owned)
+    }
+
+    fn get_first_ptr<T: Component>(&self) -> Option<*mut T> {
+        let (_, owned) = self.entities.first()?;
+        Self::component(owned as *const Q::Owned as *mut Q::Owned)
+    }
+}
+
+// The `&self` functions only read through the pointers derived from shared references, and the
+// `&mut self` functions only write through pointers derived from `&mut self`.
+impl<Q: QueryData> QueryAccess for MockQuery<Q> {
+    type Data = Q;
+
+    fn get<T: Component>(&self, entity_id: EntityId) -> Option<&T> {
+        self.get_ptr(entity_id).map(|ptr| unsafe { &*ptr })
+    }
+
+    fn get_mut<T: Component>(&mut self, entity_id: EntityId) -> Option<&mut T> {
+        let (_, owned) = self.entities.iter_mut().find(|(id, _)| *id == entity_id)?;
+        Self::component(owned).map(|ptr| unsafe { &mut *ptr })
+    }
+
+    fn get_first<T: Component>(&self) -> Option<&T> {
+        self.get_first_ptr().map(|ptr| unsafe { &*ptr })
+    }
+
+    fn get_first_mut<T: Component>(&mut self) -> Option<&mut T> {
+        let (_, owned) = self.entities.first_mut()?;
+        Self::component(owned).map(|ptr| unsafe { &mut *ptr })
+    }
+
+    fn for_each<F>(&mut self, mut f: F)
+    where
+        F: FnMut(Q::Item<'_>),
+    {
+        for (_, owned) in &mut self.entities {
+            let mut entity_data: Vec<*mut c_void> = unsafe { Q::component_ptrs(owned) }
+                .into_iter()
+                .map(|(_, ptr)| ptr)
+                .collect();
+
+            f(unsafe { Q::from_entity_data(entity_data.as_mut_ptr()) });
+        }
+    }
+
+    fn par_for_each<F>(&mut self, f: F)
+    where
+        F: Fn(Q::Item<'_>) + Send + Sync,
+    {
+        self.for_each(f);
+    }
+}
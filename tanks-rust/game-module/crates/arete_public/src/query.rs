@@ -0,0 +1,1182 @@
+use std::{
+    any::Any,
+    cmp::Ordering,
+    collections::BinaryHeap,
+    ffi::{c_char, c_int, c_void, CStr},
+    marker::PhantomData,
+    ops::{ControlFlow, Deref, DerefMut},
+    panic::catch_unwind,
+    ptr::addr_of_mut,
+    sync::{
+        atomic::{self, AtomicU64, AtomicUsize},
+        Mutex, PoisonError,
+    },
+};
+
+use crate::{Component, ComponentId, EntityId};
+
+/// A query is essentially an iterator over a number of entities, based on the specified
+/// template components. For example, a query of type `Query<&Transform>` will iterate over
+/// all the entities with a Transform component, and provide access to their `Transform` component.
+///
+/// Generic `Q` specifies the components to include in this query. Components *must* be references.
+/// If the query specifies more than one component, `Q` should be a tuple (i.e. `Query<(&A, &B)>`).
+///
+/// Iteration functions pass each entity to the provided closure as `Q::Item` (see `QueryData`),
+/// so the closure parameters are checked against the template at compile time.
+///
+/// Since queries may match the same entities, a system which accesses a component mutably in
+/// one query may not access that component in any of its other queries (i.e. a system may not
+/// take both `Query<(&A, &mut Transform)>` and `Query<(&B, &Transform)>`). The FFI codegen
+/// rejects such systems with a compile error. Split them into two systems instead: one which
+/// reads the data into a resource, and one which applies it.
+///
+/// A query is only valid during the system it is passed to. In debug builds, using a query after
+/// its system has returned (i.e. one stashed in a resource or a spawned thread) panics.
+#[repr(C)]
+pub struct Query<Q> {
+    query_handle: *mut c_void,
+    marker: PhantomData<Q>,
+    /// Outstanding `get_mut_guarded()` borrows.
+    #[cfg(debug_assertions)]
+    borrows: Mutex<Vec<(EntityId, ComponentId)>>,
+    /// The `QUERY_EPOCH` when this query was created.
+    #[cfg(debug_assertions)]
+    epoch: u64,
+}
+
+unsafe impl<Q> Send for Query<Q> {}
+unsafe impl<Q> Sync for Query<Q> {}
+
+impl<Q> Query<Q> {
+    pub fn new(query_handle: *mut c_void) -> Self {
+        Self {
+            query_handle,
+            marker: PhantomData,
+            #[cfg(debug_assertions)]
+            borrows: Mutex::new(Vec::new()),
+            #[cfg(debug_assertions)]
+            epoch: QUERY_EPOCH.load(atomic::Ordering::Relaxed),
+        }
+    }
+
+    /// Returns the query handle, checking that it is still valid.
+    ///
+    /// A query handle is only valid during the system it was passed to. In debug builds, this
+    /// panics if the engine has bumped the query epoch since the query was created (see
+    /// `_bump_query_epoch()`), i.e. if the query was stashed and used by a later system.
+    fn handle(&self) -> *mut c_void {
+        #[cfg(debug_assertions)]
+        if self.epoch != QUERY_EPOCH.load(atomic::Ordering::Relaxed) {
+            panic!("query used after the system it was passed to returned");
+        }
+
+        self.query_handle
+    }
+
+    /// Makes the next iteration of this query also visit entities which are normally excluded
+    /// (see `Disabled`), i.e. for editor tooling which needs to see the whole world. Gameplay
+    /// systems should not need this.
+    ///
+    /// This only affects the next iteration function call (i.e. `for_each` or `par_for_each`),
+    /// after which the query excludes disabled entities again:
+    ///
+    /// ```ignore
+    /// query = query.include_disabled();
+    /// query.for_each(|(transform, name)| { ... });
+    /// ```
+    pub fn include_disabled(self) -> Self {
+        unsafe {
+            _QUERY_INCLUDE_DISABLED_FN.unwrap_unchecked()(self.handle());
+        }
+
+        self
+    }
+
+    /// Returns a reference to the requested component for an entity in this query.
+    ///
+    /// `entity_id` is the entity to get the component of. `T` must be one of the components in `Q`.
+    ///
+    /// Returns `None` if the lookup failed (i.e. the entity does not exist in this query).
+    pub fn get<T: Component>(&self, entity_id: EntityId) -> Option<&T> {
+        unsafe {
+            let ptr =
+                _QUERY_GET_FN.unwrap_unchecked()(self.handle(), entity_id, T::id()) as *const T;
+            ptr.as_ref()
+        }
+    }
+
+    /// Returns a pointer to the component with the specified string id (see
+    /// `Component::string_id()`) for an entity in this query, for dynamic callers like scripting
+    /// bridges which only know component types by name.
+    ///
+    /// The component must be one of the components in `Q`. Dereferencing the pointer is unsafe:
+    /// the caller must know the component's layout (i.e. from the component reflection
+    /// metadata), must only read through it (even if `Q` accesses the component mutably), and
+    /// must not use it after the system returns.
+    ///
+    /// Returns `None` if the lookup failed (i.e. the entity does not exist in this query, or the
+    /// component is not in `Q`).
+    pub fn get_by_string_id(&self, entity_id: EntityId, string_id: &CStr) -> Option<*const c_void> {
+        let ptr = unsafe {
+            _QUERY_GET_BY_STRING_ID_FN.unwrap_unchecked()(
+                self.handle(),
+                entity_id,
+                string_id.as_ptr(),
+            )
+        };
+
+        (!ptr.is_null()).then_some(ptr)
+    }
+
+    /// Returns a mutable reference to the requested component for an entity in this query.
+    ///
+    /// `entity_id` is the entity to get the component of. `T` must be one of the components in `Q`.
+    ///
+    /// Returns `None` if the lookup failed (i.e. the entity does not exist in this query).
+    pub fn get_mut<T: Component>(&mut self, entity_id: EntityId) -> Option<&mut T> {
+        unsafe {
+            let ptr =
+                _QUERY_GET_MUT_FN.unwrap_unchecked()(self.handle(), entity_id, T::id()) as *mut T;
+            ptr.as_mut()
+        }
+    }
+
+    /// Returns a guarded mutable reference to the requested component for an entity in this query.
+    ///
+    /// Unlike `get_mut`, this only borrows the query immutably, so components of several entities
+    /// may be mutated at once. In debug builds, this panics if the same component of the same entity
+    /// is already borrowed by another guard, which would alias. The borrow is released when the
+    /// guard is dropped. Release builds skip this tracking for performance.
+    ///
+    /// Returns `None` if the lookup failed (i.e. the entity does not exist in this query).
+    ///
+    /// # Safety
+    ///
+    /// While the guard is alive, the same component of the same entity must not be accessed
+    /// through any other guard, or through any other function of this query (i.e. `get`), whose
+    /// references are not tracked. The debug tracking only catches the first case.
+    pub unsafe fn get_mut_guarded<T: Component>(
+        &self,
+        entity_id: EntityId,
+    ) -> Option<QueryMutGuard<'_, T>> {
+        let ptr = _QUERY_GET_MUT_FN.unwrap_unchecked()(self.handle(), entity_id, T::id()) as *mut T;
+
+        if ptr.is_null() {
+            return None;
+        }
+
+        // check for an outstanding borrow before creating the reference, which would alias it
+
+        #[cfg(debug_assertions)]
+        {
+            let mut borrows = self.borrows.lock().unwrap_or_else(PoisonError::into_inner);
+
+            if borrows.contains(&(entity_id, T::id())) {
+                panic!(
+                    "component {:?} of entity {} is already mutably borrowed",
+                    T::string_id(),
+                    entity_id.0,
+                );
+            }
+
+            borrows.push((entity_id, T::id()));
+        }
+
+        Some(QueryMutGuard {
+            component: &mut *ptr,
+            #[cfg(debug_assertions)]
+            entity_id,
+            #[cfg(debug_assertions)]
+            borrows: &self.borrows,
+        })
+    }
+
+    /// Returns a reference to the requested component for the first entity in query. This can be
+    /// useful when the query covers only a single entity.
+    ///
+    /// Returns `None` if the lookup failed (i.e. the query does not cover any entities).
+    pub fn get_first<T: Component>(&self) -> Option<&T> {
+        unsafe {
+            let ptr = _QUERY_GET_FIRST_FN.unwrap_unchecked()(self.handle(), T::id()) as *const T;
+            ptr.as_ref()
+        }
+    }
+
+    /// Returns a mutable reference to the requested component for the first entity in query. This
+    /// can be useful when the query covers only a single entity.
+    ///
+    /// Returns `None` if the lookup failed (i.e. the query does not cover any entities).
+    pub fn get_first_mut<T: Component>(&mut self) -> Option<&mut T> {
+        unsafe {
+            let ptr = _QUERY_GET_FIRST_MUT_FN.unwrap_unchecked()(self.handle(), T::id()) as *mut T;
+            ptr.as_mut()
+        }
+    }
+
+    /// Returns the `T` component of every entity in this query as one contiguous slice, for
+    /// vectorized processing.
+    ///
+    /// This is only possible when every matched entity is stored in the same archetype (i.e. has
+    /// exactly the same set of components). Returns `None` if the entities span multiple
+    /// archetypes, in which case use `for_each_archetype` to get the slices of each archetype,
+    /// or `for_each` / `par_for_each` instead.
+    pub fn as_slice<T: Component>(&self) -> Option<&[T]> {
+        let mut ptr = std::ptr::null();
+        let mut len = 0;
+
+        unsafe {
+            if !_QUERY_AS_SLICE_FN.unwrap_unchecked()(self.handle(), T::id(), &mut ptr, &mut len) {
+                return None;
+            }
+
+            if len == 0 {
+                Some(&[])
+            } else {
+                Some(std::slice::from_raw_parts(ptr as *const T, len))
+            }
+        }
+    }
+
+    /// Iterates over all entities in this query by calling the provided function once per entity.
+    ///
+    /// This function only runs on a single thread. Prefer `par_for_each` where possible
+    /// (see `par_for_each` docs for details).
+    ///
+    /// The parameters of the function match the order and mutability of the query template.
+    pub fn for_each<F>(&mut self, mut f: F)
+    where
+        Q: QueryData,
+        F: FnMut(Q::Item<'_>),
+    {
+        unsafe extern "C" fn callback<Q: QueryData, F: FnMut(Q::Item<'_>)>(
+            entity_data: *mut *mut c_void,
+            user_data: *mut c_void,
+        ) -> c_int {
+            match catch_unwind(|| {
+                let f = &mut *(user_data as *mut F);
+                f(Q::from_entity_data(entity_data));
+            }) {
+                Ok(..) => 0,
+                Err(..) => 1,
+            }
+        }
+
+        unsafe {
+            _QUERY_FOR_EACH_FN.unwrap_unchecked()(
+                self.handle(),
+                callback::<Q, F>,
+                &mut f as *mut _ as _,
+            );
+        }
+    }
+
+    /// Iterates over all entities in this query in reverse order, by calling the provided function
+    /// once per entity.
+    ///
+    /// Entities are visited in the exact reverse of the order `for_each` would visit them on the
+    /// same frame. The engine's iteration order is otherwise unspecified, and may change between
+    /// frames as entities are spawned and despawned, so do not rely on it across frames.
+    ///
+    /// The parameters of the function match the order and mutability of the query template.
+    pub fn for_each_rev<F>(&mut self, mut f: F)
+    where
+        Q: QueryData,
+        F: FnMut(Q::Item<'_>),
+    {
+        unsafe extern "C" fn callback<Q: QueryData, F: FnMut(Q::Item<'_>)>(
+            entity_data: *mut *mut c_void,
+            user_data: *mut c_void,
+        ) -> c_int {
+            match catch_unwind(|| {
+                let f = &mut *(user_data as *mut F);
+                f(Q::from_entity_data(entity_data));
+            }) {
+                Ok(..) => 0,
+                Err(..) => 1,
+            }
+        }
+
+        unsafe {
+            _QUERY_FOR_EACH_REV_FN.unwrap_unchecked()(
+                self.handle(),
+                callback::<Q, F>,
+                &mut f as *mut _ as _,
+            );
+        }
+    }
+
+    /// Iterates over the entities in this query like `for_each`, until the provided function
+    /// returns `ControlFlow::Break`, i.e. to stop once a matching entity has been found.
+    ///
+    /// Returns the value of the first `Break`, or `Continue` if every entity was visited. The engine
+    /// still walks the remaining entities after a `Break`, but the function is no longer called.
+    ///
+    /// The parameters of the function match the order and mutability of the query template.
+    pub fn try_for_each<B, F>(&mut self, mut f: F) -> ControlFlow<B>
+    where
+        Q: QueryData,
+        F: FnMut(Q::Item<'_>) -> ControlFlow<B>,
+    {
+        let mut result = ControlFlow::Continue(());
+
+        self.for_each(|item| {
+            if result.is_continue() {
+                result = f(item);
+            }
+        });
+
+        result
+    }
+
+    /// Iterates over all entities in this query by calling the provided function once per entity,
+    /// additionally providing the `P` component of each entity's parent (see `Parent`).
+    ///
+    /// The parent component is `None` for root entities (entities without a `Parent` component),
+    /// and for entities whose parent does not have a `P` component.
+    ///
+    /// This function only runs on a single thread.
+    ///
+    /// The parameters of the function match the order and mutability of the query template.
+    pub fn for_each_with_parent<P, F>(&mut self, mut f: F)
+    where
+        Q: QueryData,
+        P: Component,
+        F: FnMut(Q::Item<'_>, Option<&P>),
+    {
+        unsafe extern "C" fn callback<Q: QueryData, P, F: FnMut(Q::Item<'_>, Option<&P>)>(
+            entity_data: *mut *mut c_void,
+            parent_data: *const c_void,
+            user_data: *mut c_void,
+        ) -> c_int {
+            match catch_unwind(|| {
+                let f = &mut *(user_data as *mut F);
+                f(
+                    Q::from_entity_data(entity_data),
+                    (parent_data as *const P).as_ref(),
+                );
+            }) {
+                Ok(..) => 0,
+                Err(..) => 1,
+            }
+        }
+
+        unsafe {
+            _QUERY_FOR_EACH_WITH_PARENT_FN.unwrap_unchecked()(
+                self.handle(),
+                P::id(),
+                callback::<Q, P, F>,
+                &mut f as *mut _ as _,
+            );
+        }
+    }
+
+    /// Returns the entity for which `key` returns the largest value, or `None` if the query is
+    /// empty. If several entities tie, which of them is returned is unspecified.
+    ///
+    /// Keys which are not comparable to themselves (i.e. `f32::NAN`) are skipped, so float keys
+    /// work as expected as long as at least one key is not NaN.
+    ///
+    /// This function only runs on a single thread.
+    pub fn max_by_key<K, F>(&mut self, key: F) -> Option<EntityId>
+    where
+        Q: QueryData,
+        K: PartialOrd,
+        F: FnMut(Q::Item<'_>) -> K,
+    {
+        self.best_by_key(key, |key, best| key > best)
+    }
+
+    /// Returns the entity for which `key` returns the smallest value, or `None` if the query is
+    /// empty. If several entities tie, which of them is returned is unspecified.
+    ///
+    /// Keys which are not comparable to themselves (i.e. `f32::NAN`) are skipped, so float keys
+    /// work as expected as long as at least one key is not NaN.
+    ///
+    /// This function only runs on a single thread.
+    pub fn min_by_key<K, F>(&mut self, key: F) -> Option<EntityId>
+    where
+        Q: QueryData,
+        K: PartialOrd,
+        F: FnMut(Q::Item<'_>) -> K,
+    {
+        self.best_by_key(key, |key, best| key < best)
+    }
+
+    /// Returns the (up to) `n` entities for which `key` returns the smallest values, sorted by
+    /// ascending key. If several entities tie, which of them are returned is unspecified.
+    ///
+    /// This keeps only the best `n` entities while iterating, so it costs O(m log n) time for
+    /// `m` entities and allocates only for `n` entries, rather than collecting and sorting all of
+    /// them.
+    ///
+    /// This function only runs on a single thread.
+    pub fn take_sorted_by_key<K, F>(&mut self, n: usize, mut key: F) -> Vec<EntityId>
+    where
+        Q: QueryData,
+        K: Ord,
+        F: FnMut(Q::Item<'_>) -> K,
+    {
+        if n == 0 {
+            return Vec::new();
+        }
+
+        // a max-heap, so the worst of the best `n` entities so far is on top
+        let mut heap = BinaryHeap::new();
+
+        self.for_each_with_id(|item, entity_id| {
+            let entry = KeyedEntity {
+                key: key(item),
+                entity_id,
+            };
+
+            if heap.len() < n {
+                heap.push(entry);
+            } else if let Some(mut worst) = heap.peek_mut() {
+                if entry.key < worst.key {
+                    *worst = entry;
+                }
+            }
+        });
+
+        heap.into_sorted_vec()
+            .into_iter()
+            .map(|entry| entry.entity_id)
+            .collect()
+    }
+
+    fn best_by_key<K, F>(&mut self, mut key: F, is_better: fn(&K, &K) -> bool) -> Option<EntityId>
+    where
+        Q: QueryData,
+        K: PartialOrd,
+        F: FnMut(Q::Item<'_>) -> K,
+    {
+        let mut best: Option<(K, EntityId)> = None;
+
+        self.for_each_with_id(|item, entity_id| {
+            let key = key(item);
+
+            if key.partial_cmp(&key).is_none() {
+                return;
+            }
+
+            let is_best = match &best {
+                Some((best, _)) => is_better(&key, best),
+                None => true,
+            };
+
+            if is_best {
+                best = Some((key, entity_id));
+            }
+        });
+
+        best.map(|(_, entity_id)| entity_id)
+    }
+
+    /// Like `for_each`, but additionally provides the `EntityId` of each entity.
+    fn for_each_with_id<F>(&mut self, mut f: F)
+    where
+        Q: QueryData,
+        F: FnMut(Q::Item<'_>, EntityId),
+    {
+        unsafe extern "C" fn callback<Q: QueryData, F: FnMut(Q::Item<'_>, EntityId)>(
+            entity_data: *mut *mut c_void,
+            entity_id: EntityId,
+            user_data: *mut c_void,
+        ) -> c_int {
+            match catch_unwind(|| {
+                let f = &mut *(user_data as *mut F);
+                f(Q::from_entity_data(entity_data), entity_id);
+            }) {
+                Ok(..) => 0,
+                Err(..) => 1,
+            }
+        }
+
+        unsafe {
+            _QUERY_FOR_EACH_WITH_ID_FN.unwrap_unchecked()(
+                self.handle(),
+                callback::<Q, F>,
+                &mut f as *mut _ as _,
+            );
+        }
+    }
+
+    /// Iterates over all entities in this query by calling the provided function once per entity.
+    ///
+    /// This version of for-each will be run in parallel and can provide significant performance improvements.
+    /// This version should be the default, unless it is necessary to mutate captured state.
+    ///
+    /// The parameters of the function match the order and mutability of the query template.
+    ///
+    /// If the function panics, the panic message is recorded for the engine to log (see
+    /// `ParForEachPanic`), and the engine stops the iteration.
+    pub fn par_for_each<F>(&mut self, f: F)
+    where
+        Q: QueryData,
+        F: Fn(Q::Item<'_>) + Send + Sync,
+    {
+        struct Context<F> {
+            f: F,
+            entity_index: AtomicUsize,
+        }
+
+        unsafe extern "C" fn callback<Q: QueryData, F: Fn(Q::Item<'_>)>(
+            entity_data: *mut *mut c_void,
+            user_data: *const c_void,
+        ) -> c_int {
+            let entity_index = (*(user_data as *const Context<F>))
+                .entity_index
+                .fetch_add(1, atomic::Ordering::Relaxed);
+
+            match catch_unwind(|| {
+                let context = &*(user_data as *const Context<F>);
+                (context.f)(Q::from_entity_data(entity_data));
+            }) {
+                Ok(..) => 0,
+                Err(payload) => {
+                    record_par_for_each_panic(entity_index, payload);
+                    1
+                }
+            }
+        }
+
+        let context = Context {
+            f,
+            entity_index: AtomicUsize::new(0),
+        };
+
+        unsafe {
+            _QUERY_PAR_FOR_EACH_FN.unwrap_unchecked()(
+                self.handle(),
+                callback::<Q, F>,
+                &context as *const _ as _,
+            );
+        }
+    }
+
+    /// Iterates over the archetypes matched by this query, calling the provided function once
+    /// per archetype with its entities' components as contiguous slices (see `ArchetypeChunk`).
+    ///
+    /// This is the lower-level primitive beneath `for_each` and `par_for_each`, which the engine
+    /// implements by walking the same columns. Processing a whole column at once keeps it in
+    /// cache and lets the compiler vectorize the loop, at the cost of handling archetypes
+    /// explicitly: i.e. the entity at index `i` of one slice is the entity at index `i` of every
+    /// other slice of the same chunk, but not of other chunks. Archetypes without entities are
+    /// skipped.
+    ///
+    /// ```ignore
+    /// query.for_each_archetype(|chunk| {
+    ///     let (transforms, velocities) = chunk.columns();
+    ///     for (transform, velocity) in transforms.iter_mut().zip(velocities) {
+    ///         transform.position += velocity.val * dt;
+    ///     }
+    /// });
+    /// ```
+    ///
+    /// This function only runs on a single thread.
+    pub fn for_each_archetype<F>(&mut self, mut f: F)
+    where
+        Q: QueryData,
+        F: FnMut(ArchetypeChunk<'_, Q>),
+    {
+        unsafe extern "C" fn callback<Q: QueryData, F: FnMut(ArchetypeChunk<'_, Q>)>(
+            columns: *mut *mut c_void,
+            len: usize,
+            user_data: *mut c_void,
+        ) -> c_int {
+            match catch_unwind(|| {
+                let f = &mut *(user_data as *mut F);
+                f(ArchetypeChunk {
+                    columns,
+                    len,
+                    marker: PhantomData,
+                });
+            }) {
+                Ok(..) => 0,
+                Err(..) => 1,
+            }
+        }
+
+        unsafe {
+            _QUERY_FOR_EACH_ARCHETYPE_FN.unwrap_unchecked()(
+                self.handle(),
+                callback::<Q, F>,
+                &mut f as *mut _ as _,
+            );
+        }
+    }
+}
+
+/// The entities of one archetype matched by a query, passed to `Query::for_each_archetype`.
+pub struct ArchetypeChunk<'a, Q: QueryData> {
+    /// The base pointer of each component column, in template order.
+    columns: *mut *mut c_void,
+    len: usize,
+    marker: PhantomData<(&'a mut (), Q)>,
+}
+
+impl<'a, Q: QueryData> ArchetypeChunk<'a, Q> {
+    /// Returns the number of entities in this chunk.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Returns one slice per component of the template, in template order, with the template's
+    /// mutability, i.e. `(&mut [Transform], &[Velocity])` for `Query<(&mut Transform, &Velocity)>`.
+    /// `With<T>` filters yield `()`.
+    pub fn columns(self) -> Q::Slices<'a> {
+        unsafe { Q::from_columns(self.columns, self.len) }
+    }
+}
+
+/// The lookup and iteration surface shared by `Query` and `MockQuery` (with the `test-util`
+/// feature), so gameplay logic written against it can be unit tested without the engine.
+///
+/// Systems must still take a `Query`, but may pass it on to a function generic over
+/// `QueryAccess` (see `MockQuery` for the intended test pattern). Each function behaves like the
+/// `Query` function of the same name. This also allows reusable helpers which work with any
+/// query, i.e. `fn count<'a>(query: &mut impl QueryAccess<Data = &'a Transform>) -> usize`.
+///
+/// `QueryAccess` is not object safe, since its functions are generic over the component type
+/// (i.e. `get<T>`) and the closure type, so it cannot be used as `dyn QueryAccess`. Write helpers
+/// generic over the query type (`impl QueryAccess`) instead.
+pub trait QueryAccess {
+    type Data: QueryData;
+
+    fn get<T: Component>(&self, entity_id: EntityId) -> Option<&T>;
+
+    fn get_mut<T: Component>(&mut self, entity_id: EntityId) -> Option<&mut T>;
+
+    fn get_first<T: Component>(&self) -> Option<&T>;
+
+    fn get_first_mut<T: Component>(&mut self) -> Option<&mut T>;
+
+    fn for_each<F>(&mut self, f: F)
+    where
+        F: FnMut(<Self::Data as QueryData>::Item<'_>);
+
+    fn par_for_each<F>(&mut self, f: F)
+    where
+        F: Fn(<Self::Data as QueryData>::Item<'_>) + Send + Sync;
+}
+
+impl<Q: QueryData> QueryAccess for Query<Q> {
+    type Data = Q;
+
+    fn get<T: Component>(&self, entity_id: EntityId) -> Option<&T> {
+        Query::get(self, entity_id)
+    }
+
+    fn get_mut<T: Component>(&mut self, entity_id: EntityId) -> Option<&mut T> {
+        Query::get_mut(self, entity_id)
+    }
+
+    fn get_first<T: Component>(&self) -> Option<&T> {
+        Query::get_first(self)
+    }
+
+    fn get_first_mut<T: Component>(&mut self) -> Option<&mut T> {
+        Query::get_first_mut(self)
+    }
+
+    fn for_each<F>(&mut self, f: F)
+    where
+        F: FnMut(Q::Item<'_>),
+    {
+        Query::for_each(self, f);
+    }
+
+    fn par_for_each<F>(&mut self, f: F)
+    where
+        F: Fn(Q::Item<'_>) + Send + Sync,
+    {
+        Query::par_for_each(self, f);
+    }
+}
+
+/// Incremented by the engine between systems, invalidating the queries of previous systems.
+static QUERY_EPOCH: AtomicU64 = AtomicU64::new(0);
+
+/// Invalidates every existing `Query`. This is called by the FFI layer whenever no system is
+/// running (i.e. between systems, or batches of systems which run in parallel). Stale queries
+/// are only detected in debug builds.
+pub fn _bump_query_epoch() {
+    QUERY_EPOCH.fetch_add(1, atomic::Ordering::Relaxed);
+}
+
+/// An entity ordered by its key only, for `Query::take_sorted_by_key()`.
+struct KeyedEntity<K> {
+    key: K,
+    entity_id: EntityId,
+}
+
+impl<K: Ord> PartialEq for KeyedEntity<K> {
+    fn eq(&self, other: &Self) -> bool {
+        self.key == other.key
+    }
+}
+
+impl<K: Ord> Eq for KeyedEntity<K> {}
+
+impl<K: Ord> PartialOrd for KeyedEntity<K> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<K: Ord> Ord for KeyedEntity<K> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.key.cmp(&other.key)
+    }
+}
+
+/// Details of a panic caught in a `Query::par_for_each()` function.
+#[derive(Clone, Debug)]
+pub struct ParForEachPanic {
+    /// The number of entities the iteration had started on before the panicking one. Since
+    /// entities are visited by several threads at once, this is only approximate.
+    pub entity_index: usize,
+    /// The panic message, if the panic payload was a string.
+    pub message: String,
+}
+
+/// The first panic caught since the engine last called `_take_par_for_each_panic()`.
+static PAR_FOR_EACH_PANIC: Mutex<Option<ParForEachPanic>> = Mutex::new(None);
+
+fn record_par_for_each_panic(entity_index: usize, payload: Box<dyn Any + Send>) {
+    let message = if let Some(message) = payload.downcast_ref::<&str>() {
+        message.to_string()
+    } else if let Some(message) = payload.downcast_ref::<String>() {
+        message.clone()
+    } else {
+        String::new()
+    };
+
+    let mut slot = PAR_FOR_EACH_PANIC
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner);
+
+    // when several threads panic, the first panic is usually the cause of the others
+    if slot.is_none() {
+        *slot = Some(ParForEachPanic {
+            entity_index,
+            message,
+        });
+    }
+}
+
+/// Takes the details of the first panic caught in a `Query::par_for_each()` function since the
+/// last call. This is called by the FFI layer, so that the engine can log which system panicked.
+pub fn _take_par_for_each_panic() -> Option<ParForEachPanic> {
+    PAR_FOR_EACH_PANIC
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .take()
+}
+
+/// A mutable reference to a component, returned by `Query::get_mut_guarded()`.
+pub struct QueryMutGuard<'a, T: Component> {
+    component: &'a mut T,
+    #[cfg(debug_assertions)]
+    entity_id: EntityId,
+    #[cfg(debug_assertions)]
+    borrows: &'a Mutex<Vec<(EntityId, ComponentId)>>,
+}
+
+impl<T: Component> Deref for QueryMutGuard<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &Self::Target {
+        self.component
+    }
+}
+
+impl<T: Component> DerefMut for QueryMutGuard<'_, T> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        self.component
+    }
+}
+
+impl<T: Component> Drop for QueryMutGuard<'_, T> {
+    fn drop(&mut self) {
+        #[cfg(debug_assertions)]
+        {
+            let mut borrows = self.borrows.lock().unwrap_or_else(PoisonError::into_inner);
+            let borrow = (self.entity_id, T::id());
+
+            if let Some(i) = borrows.iter().position(|b| *b == borrow) {
+                borrows.swap_remove(i);
+            }
+        }
+    }
+}
+
+/// A query template: a reference to a component or a `With<T>` filter, or a tuple of those.
+///
+/// `Item` is the type passed to the closures of `Query::for_each()` and friends. It matches the
+/// template exactly, except that its references only live for the duration of the closure call.
+/// For example, the items of `Query<(&A, &mut B)>` are `(&A, &mut B)`, so a closure which
+/// mutates `A` is rejected at compile time.
+///
+/// NOTE: closures previously received `Q` itself. Closures which relied on component references
+/// outliving the call must now copy the component data instead.
+pub trait QueryData {
+    type Item<'a>;
+
+    /// The components of the template by value, i.e. `(A, B)` for `(&A, &mut B)`. Used by
+    /// `MockQuery` to store entities.
+    type Owned;
+
+    /// The component columns of an archetype, i.e. `(&[A], &mut [B])` for `(&A, &mut B)`. See
+    /// `ArchetypeChunk`.
+    type Slices<'a>;
+
+    /// Creates the item from the component pointers provided by the engine.
+    ///
+    /// # Safety
+    ///
+    /// `entity_data` must point to a valid pointer for each component of the template, in order,
+    /// with the access (shared or mutable) declared by the template.
+    unsafe fn from_entity_data<'a>(entity_data: *mut *mut c_void) -> Self::Item<'a>;
+
+    /// Returns the string id and address of each component in `owned`, in template order. This
+    /// is the `entity_data` which `from_entity_data` expects, for entities stored by `MockQuery`.
+    ///
+    /// # Safety
+    ///
+    /// `owned` must point to a valid `Self::Owned`.
+    unsafe fn component_ptrs(owned: *mut Self::Owned) -> Vec<(&'static CStr, *mut c_void)>;
+
+    /// Creates the slices from the column pointers provided by the engine.
+    ///
+    /// # Safety
+    ///
+    /// `columns` must point to a valid pointer to `len` contiguous components for each component
+    /// of the template, in order, with the access declared by the template.
+    unsafe fn from_columns<'a>(columns: *mut *mut c_void, len: usize) -> Self::Slices<'a>;
+}
+
+/// A single component reference of a query template. See `QueryData`.
+pub trait QueryComponent {
+    type Item<'a>;
+
+    /// The referenced component type.
+    type Component: Component;
+
+    /// The column of the referenced component, i.e. `&'a [T]` for `&T`.
+    type Slice<'a>;
+
+    /// # Safety
+    ///
+    /// `component` must point to a valid component, with the access declared by the template.
+    unsafe fn from_ptr<'a>(component: *mut c_void) -> Self::Item<'a>;
+
+    /// # Safety
+    ///
+    /// `column` must point to `len` contiguous components, with the access declared by the
+    /// template.
+    unsafe fn slice_from_ptr<'a>(column: *mut c_void, len: usize) -> Self::Slice<'a>;
+}
+
+impl<T: Component + 'static> QueryComponent for &T {
+    type Item<'a> = &'a T;
+    type Component = T;
+    type Slice<'a> = &'a [T];
+
+    unsafe fn from_ptr<'a>(component: *mut c_void) -> Self::Item<'a> {
+        &*(component as *const T)
+    }
+
+    unsafe fn slice_from_ptr<'a>(column: *mut c_void, len: usize) -> Self::Slice<'a> {
+        if len == 0 {
+            &[]
+        } else {
+            std::slice::from_raw_parts(column as *const T, len)
+        }
+    }
+}
+
+impl<T: Component + 'static> QueryComponent for &mut T {
+    type Item<'a> = &'a mut T;
+    type Component = T;
+    type Slice<'a> = &'a mut [T];
+
+    unsafe fn from_ptr<'a>(component: *mut c_void) -> Self::Item<'a> {
+        &mut *(component as *mut T)
+    }
+
+    unsafe fn slice_from_ptr<'a>(column: *mut c_void, len: usize) -> Self::Slice<'a> {
+        if len == 0 {
+            &mut []
+        } else {
+            std::slice::from_raw_parts_mut(column as *mut T, len)
+        }
+    }
+}
+
+impl<T: Component + 'static> QueryData for &T {
+    type Item<'a> = &'a T;
+    type Owned = T;
+    type Slices<'a> = &'a [T];
+
+    unsafe fn from_entity_data<'a>(entity_data: *mut *mut c_void) -> Self::Item<'a> {
+        <Self as QueryComponent>::from_ptr(*entity_data)
+    }
+
+    unsafe fn component_ptrs(owned: *mut Self::Owned) -> Vec<(&'static CStr, *mut c_void)> {
+        vec![(T::string_id(), owned as *mut c_void)]
+    }
+
+    unsafe fn from_columns<'a>(columns: *mut *mut c_void, len: usize) -> Self::Slices<'a> {
+        <Self as QueryComponent>::slice_from_ptr(*columns, len)
+    }
+}
+
+impl<T: Component + 'static> QueryData for &mut T {
+    type Item<'a> = &'a mut T;
+    type Owned = T;
+    type Slices<'a> = &'a mut [T];
+
+    unsafe fn from_entity_data<'a>(entity_data: *mut *mut c_void) -> Self::Item<'a> {
+        <Self as QueryComponent>::from_ptr(*entity_data)
+    }
+
+    unsafe fn component_ptrs(owned: *mut Self::Owned) -> Vec<(&'static CStr, *mut c_void)> {
+        vec![(T::string_id(), owned as *mut c_void)]
+    }
+
+    unsafe fn from_columns<'a>(columns: *mut *mut c_void, len: usize) -> Self::Slices<'a> {
+        <Self as QueryComponent>::slice_from_ptr(*columns, len)
+    }
+}
+
+/// A query template filter, which restricts a query to entities with component `T` without
+/// accessing it, i.e. `Query<(&Transform, With<PlayerTank>)>`. Its item is `()`.
+///
+/// Querying `With<Disabled>` is also how a query opts in to matching disabled entities (see
+/// `Disabled`).
+pub struct With<T>(PhantomData<T>);
+
+impl<T: Component + 'static> QueryComponent for With<T> {
+    type Item<'a> = ();
+    type Component = T;
+    type Slice<'a> = ();
+
+    unsafe fn from_ptr<'a>(_component: *mut c_void) -> Self::Item<'a> {}
+
+    unsafe fn slice_from_ptr<'a>(_column: *mut c_void, _len: usize) -> Self::Slice<'a> {}
+}
+
+impl<T: Component + 'static> QueryData for With<T> {
+    type Item<'a> = ();
+    type Owned = T;
+    type Slices<'a> = ();
+
+    unsafe fn from_entity_data<'a>(_entity_data: *mut *mut c_void) -> Self::Item<'a> {}
+
+    unsafe fn component_ptrs(owned: *mut Self::Owned) -> Vec<(&'static CStr, *mut c_void)> {
+        vec![(T::string_id(), owned as *mut c_void)]
+    }
+
+    unsafe fn from_columns<'a>(_columns: *mut *mut c_void, _len: usize) -> Self::Slices<'a> {}
+}
+
+macro_rules! impl_query_data_tuple {
+    ($($t:ident $i:tt),*) => {
+        impl<$($t: QueryComponent),*> QueryData for ($($t,)*) {
+            type Item<'a> = ($($t::Item<'a>,)*);
+            type Owned = ($($t::Component,)*);
+            type Slices<'a> = ($($t::Slice<'a>,)*);
+
+            unsafe fn from_entity_data<'a>(entity_data: *mut *mut c_void) -> Self::Item<'a> {
+                ($($t::from_ptr(*entity_data.add($i)),)*)
+            }
+
+            unsafe fn component_ptrs(owned: *mut Self::Owned) -> Vec<(&'static CStr, *mut c_void)> {
+                vec![$((
+                    <$t::Component as Component>::string_id(),
+                    addr_of_mut!((*owned).$i) as *mut c_void,
+                )),*]
+            }
+
+            unsafe fn from_columns<'a>(columns: *mut *mut c_void, len: usize) -> Self::Slices<'a> {
+                ($($t::slice_from_ptr(*columns.add($i), len),)*)
+            }
+        }
+    };
+}
+
+impl_query_data_tuple!(A 0);
+impl_query_data_tuple!(A 0, B 1);
+impl_query_data_tuple!(A 0, B 1, C 2);
+impl_query_data_tuple!(A 0, B 1, C 2, D 3);
+impl_query_data_tuple!(A 0, B 1, C 2, D 3, E 4);
+impl_query_data_tuple!(A 0, B 1, C 2, D 3, E 4, F 5);
+impl_query_data_tuple!(A 0, B 1, C 2, D 3, E 4, F 5, G 6);
+impl_query_data_tuple!(A 0, B 1, C 2, D 3, E 4, F 5, G 6, H 7);
+impl_query_data_tuple!(A 0, B 1, C 2, D 3, E 4, F 5, G 6, H 7, I 8);
+impl_query_data_tuple!(A 0, B 1, C 2, D 3, E 4, F 5, G 6, H 7, I 8, J 9);
+impl_query_data_tuple!(A 0, B 1, C 2, D 3, E 4, F 5, G 6, H 7, I 8, J 9, K 10);
+impl_query_data_tuple!(A 0, B 1, C 2, D 3, E 4, F 5, G 6, H 7, I 8, J 9, K 10, L 11);
+
+// global callback functions, set by the generated `set_callback_fn`
+
+pub type _QueryGetFn = unsafe extern "C" fn(*const c_void, EntityId, ComponentId) -> *const c_void;
+
+pub static mut _QUERY_GET_FN: Option<_QueryGetFn> = None;
+
+pub type _QueryGetByStringIdFn =
+    unsafe extern "C" fn(*const c_void, EntityId, *const c_char) -> *const c_void;
+
+pub static mut _QUERY_GET_BY_STRING_ID_FN: Option<_QueryGetByStringIdFn> = None;
+
+pub type _QueryIncludeDisabledFn = unsafe extern "C" fn(*mut c_void);
+
+pub static mut _QUERY_INCLUDE_DISABLED_FN: Option<_QueryIncludeDisabledFn> = None;
+
+pub type _QueryGetMutFn = unsafe extern "C" fn(*mut c_void, EntityId, ComponentId) -> *mut c_void;
+
+pub static mut _QUERY_GET_MUT_FN: Option<_QueryGetMutFn> = None;
+
+pub type _QueryGetFirstFn = unsafe extern "C" fn(*const c_void, ComponentId) -> *const c_void;
+
+pub static mut _QUERY_GET_FIRST_FN: Option<_QueryGetFirstFn> = None;
+
+pub type _QueryGetFirstMutFn = unsafe extern "C" fn(*mut c_void, ComponentId) -> *mut c_void;
+
+pub static mut _QUERY_GET_FIRST_MUT_FN: Option<_QueryGetFirstMutFn> = None;
+
+pub type _QueryAsSliceFn =
+    unsafe extern "C" fn(*const c_void, ComponentId, *mut *const c_void, *mut usize) -> bool;
+
+pub static mut _QUERY_AS_SLICE_FN: Option<_QueryAsSliceFn> = None;
+
+pub type _QueryForEachFn = unsafe extern "C" fn(
+    *mut c_void,
+    unsafe extern "C" fn(*mut *mut c_void, *mut c_void) -> c_int,
+    *mut c_void,
+);
+
+pub static mut _QUERY_FOR_EACH_FN: Option<_QueryForEachFn> = None;
+
+pub type _QueryForEachRevFn = unsafe extern "C" fn(
+    *mut c_void,
+    unsafe extern "C" fn(*mut *mut c_void, *mut c_void) -> c_int,
+    *mut c_void,
+);
+
+pub static mut _QUERY_FOR_EACH_REV_FN: Option<_QueryForEachRevFn> = None;
+
+pub type _QueryForEachWithIdFn = unsafe extern "C" fn(
+    *mut c_void,
+    unsafe extern "C" fn(*mut *mut c_void, EntityId, *mut c_void) -> c_int,
+    *mut c_void,
+);
+
+pub static mut _QUERY_FOR_EACH_WITH_ID_FN: Option<_QueryForEachWithIdFn> = None;
+
+pub type _QueryParForEachFn = unsafe extern "C" fn(
+    *mut c_void,
+    unsafe extern "C" fn(*mut *mut c_void, *const c_void) -> c_int,
+    *const c_void,
+);
+
+pub static mut _QUERY_PAR_FOR_EACH_FN: Option<_QueryParForEachFn> = None;
+
+pub type _QueryForEachArchetypeFn = unsafe extern "C" fn(
+    *mut c_void,
+    unsafe extern "C" fn(*mut *mut c_void, usize, *mut c_void) -> c_int,
+    *mut c_void,
+);
+
+pub static mut _QUERY_FOR_EACH_ARCHETYPE_FN: Option<_QueryForEachArchetypeFn> = None;
+
+pub type _QueryForEachWithParentFn = unsafe extern "C" fn(
+    *mut c_void,
+    ComponentId,
+    unsafe extern "C" fn(*mut *mut c_void, *const c_void, *mut c_void) -> c_int,
+    *mut c_void,
+);
+
+pub static mut _QUERY_FOR_EACH_WITH_PARENT_FN: Option<_QueryForEachWithParentFn> = None;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::RenderLayer;
+
+    static mut LAYER: RenderLayer = RenderLayer(1);
+
+    unsafe extern "C" fn get_mut(_: *mut c_void, _: EntityId, _: ComponentId) -> *mut c_void {
+        addr_of_mut!(LAYER) as *mut c_void
+    }
+
+    unsafe extern "C" fn for_each(
+        _: *mut c_void,
+        callback: unsafe extern "C" fn(*mut *mut c_void, *mut c_void) -> c_int,
+        user_data: *mut c_void,
+    ) {
+        for mut layer in (1..=4).map(RenderLayer) {
+            let mut entity_data = [addr_of_mut!(layer) as *mut c_void];
+            callback(entity_data.as_mut_ptr(), user_data);
+        }
+    }
+
+    #[test]
+    fn try_for_each_stops_at_break() {
+        let _globals = crate::tests::lock_globals();
+
+        unsafe {
+            _QUERY_FOR_EACH_FN = Some(for_each);
+        }
+
+        let mut query = Query::<&RenderLayer>::new(std::ptr::null_mut());
+
+        let mut visited = Vec::new();
+        let result = query.try_for_each(|layer| {
+            visited.push(layer.0);
+
+            if layer.0 == 2 {
+                ControlFlow::Break(layer.0 * 10)
+            } else {
+                ControlFlow::Continue(())
+            }
+        });
+
+        assert_eq!(result, ControlFlow::Break(20));
+        assert_eq!(visited, [1, 2]);
+
+        // without a `Break`, every entity is visited
+        let mut count = 0;
+        let result: ControlFlow<()> = query.try_for_each(|_| {
+            count += 1;
+            ControlFlow::Continue(())
+        });
+
+        assert_eq!(result, ControlFlow::Continue(()));
+        assert_eq!(count, 4);
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic(expected = "already mutably borrowed")]
+    fn get_mut_guarded_twice_panics() {
+        let _globals = crate::tests::lock_globals();
+
+        unsafe {
+            _QUERY_GET_MUT_FN = Some(get_mut);
+        }
+
+        let query = Query::<&mut RenderLayer>::new(std::ptr::null_mut());
+
+        unsafe {
+            let _first = query.get_mut_guarded::<RenderLayer>(EntityId(1));
+            let _second = query.get_mut_guarded::<RenderLayer>(EntityId(1));
+        }
+    }
+}
@@ -0,0 +1,50 @@
+use std::{ffi::c_void, marker::PhantomData, slice};
+
+use crate::{Component, EntityId};
+
+/// A system input which provides the entities which had component `T` removed, either by
+/// `Engine::remove_component()` or by being despawned. This allows systems to release resources
+/// associated with a component (i.e. an engine handle stored in the component).
+///
+/// Removals are applied at the end of the frame, and each removal is retained for exactly one
+/// frame: the removals applied at the end of one frame are provided on the following frame, and
+/// then discarded. The removed component data is no longer readable.
+///
+/// Like `Query`, this input must be taken by value (i.e. `removed: Removed<Sound>`).
+#[repr(C)]
+pub struct Removed<T> {
+    removed_handle: *mut c_void,
+    marker: PhantomData<T>,
+}
+
+unsafe impl<T> Send for Removed<T> {}
+unsafe impl<T> Sync for Removed<T> {}
+
+impl<T: Component> Removed<T> {
+    pub fn new(removed_handle: *mut c_void) -> Self {
+        Self {
+            removed_handle,
+            marker: PhantomData,
+        }
+    }
+
+    /// Returns the entities which had component `T` removed last frame.
+    pub fn entities(&self) -> &[EntityId] {
+        unsafe {
+            let mut len = 0;
+            let ptr = _REMOVED_ENTITIES_FN.unwrap_unchecked()(self.removed_handle, &mut len);
+
+            if ptr.is_null() {
+                &[]
+            } else {
+                slice::from_raw_parts(ptr, len)
+            }
+        }
+    }
+}
+
+// global callback functions
+
+pub type _RemovedEntitiesFn = unsafe extern "C" fn(*mut c_void, *mut usize) -> *const EntityId;
+
+pub static mut _REMOVED_ENTITIES_FN: Option<_RemovedEntitiesFn> = None;
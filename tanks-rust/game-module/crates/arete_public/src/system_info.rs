@@ -0,0 +1,149 @@
+use std::ffi::{c_char, CStr};
+
+/// The type of a system input, or of an element of a query template. The FFI codegen returns
+/// this to the engine, so new variants must be appended.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ArgType {
+    /// A mutable component or resource reference (`&mut T`).
+    DataAccessMut,
+    /// A shared component or resource reference (`&T`).
+    DataAccessRef,
+    /// A `Query` input.
+    Query,
+    /// A `Removed` input.
+    Removed,
+    /// `&EntityId` in a query template.
+    EntityId,
+    /// A `With<T>` filter in a query template.
+    With,
+}
+
+/// The structure of a system, as returned by `system_info()`.
+#[derive(Clone, Debug)]
+pub struct SystemInfo {
+    /// The name of the system function.
+    pub name: &'static CStr,
+    /// `true` for `#[system_once]` systems.
+    pub is_once: bool,
+    /// The inputs of the system, in parameter order.
+    pub args: Vec<SystemArg>,
+}
+
+/// An input of a system, or an element of a query template.
+#[derive(Clone, Debug)]
+pub struct SystemArg {
+    pub arg_type: ArgType,
+    /// The string id of the component or resource (see `Component::string_id()`). This is
+    /// `None` for queries, and for `&EntityId` in a query.
+    pub component: Option<&'static CStr>,
+    /// The elements of the query template, if `arg_type` is `ArgType::Query`.
+    pub query_args: Vec<SystemArg>,
+}
+
+// generated by the FFI codegen, and linked into the same module. A crate which links arete_public
+// without the codegen (i.e. a tool or test binary) doesn't define these, and fails to link if it
+// calls any of the public functions below.
+mod ffi {
+    use std::ffi::c_char;
+
+    use super::ArgType;
+
+    extern "C" {
+        pub fn systems_len() -> usize;
+        pub fn system_name(system_index: usize) -> *const c_char;
+        pub fn system_is_once(system_index: usize) -> bool;
+        pub fn system_did_run(system_index: usize) -> bool;
+        pub fn system_args_len(system_index: usize) -> usize;
+        pub fn system_arg_type(system_index: usize, arg_index: usize) -> ArgType;
+        pub fn system_arg_component(system_index: usize, arg_index: usize) -> *const c_char;
+        pub fn system_query_args_len(system_index: usize, arg_index: usize) -> usize;
+        pub fn system_query_arg_type(
+            system_index: usize,
+            arg_index: usize,
+            query_index: usize,
+        ) -> ArgType;
+        pub fn system_query_arg_component(
+            system_index: usize,
+            arg_index: usize,
+            query_index: usize,
+        ) -> *const c_char;
+    }
+}
+
+/// Returns the number of systems in this module, including `#[system_once]` systems and the
+/// systems added for events.
+///
+/// This links against the functions generated by the FFI codegen, so it is only available in
+/// a game module built with it. Calling it from any other crate fails at link time.
+pub fn systems_len() -> usize {
+    unsafe { ffi::systems_len() }
+}
+
+/// Returns the structure of the system at `index` (in `0..systems_len()`), i.e. for a debug
+/// overlay listing which components each system accesses. Returns `None` if `index` is out of
+/// range.
+///
+/// This reflects the compile-time structure generated by the FFI codegen, not runtime state:
+/// i.e. a system disabled with `Engine::set_system_enabled()` is still listed.
+///
+/// Like `systems_len()`, this is only available in a game module built with the FFI codegen.
+pub fn system_info(index: usize) -> Option<SystemInfo> {
+    if index >= systems_len() {
+        return None;
+    }
+
+    unsafe {
+        let args = (0..ffi::system_args_len(index))
+            .map(|arg| {
+                let arg_type = ffi::system_arg_type(index, arg);
+
+                if arg_type != ArgType::Query {
+                    return SystemArg {
+                        arg_type,
+                        component: string_id(ffi::system_arg_component(index, arg)),
+                        query_args: Vec::new(),
+                    };
+                }
+
+                let query_args = (0..ffi::system_query_args_len(index, arg))
+                    .map(|i| SystemArg {
+                        arg_type: ffi::system_query_arg_type(index, arg, i),
+                        component: string_id(ffi::system_query_arg_component(index, arg, i)),
+                        query_args: Vec::new(),
+                    })
+                    .collect();
+
+                SystemArg {
+                    arg_type,
+                    component: None,
+                    query_args,
+                }
+            })
+            .collect();
+
+        Some(SystemInfo {
+            name: CStr::from_ptr(ffi::system_name(index)),
+            is_once: ffi::system_is_once(index),
+            args,
+        })
+    }
+}
+
+/// Returns whether the system at `index` (in `0..systems_len()`) ran on the most recent frame,
+/// i.e. to show which systems were disabled with `Engine::set_system_enabled()`. Earlier frames
+/// are not tracked. Returns `None` if `index` is out of range.
+///
+/// Like `systems_len()`, this is only available in a game module built with the FFI codegen.
+pub fn system_did_run(index: usize) -> Option<bool> {
+    if index >= systems_len() {
+        return None;
+    }
+
+    Some(unsafe { ffi::system_did_run(index) })
+}
+
+/// Converts a string id returned by the FFI codegen, which is null for `&EntityId`.
+unsafe fn string_id(ptr: *const c_char) -> Option<&'static CStr> {
+    (!ptr.is_null()).then(|| CStr::from_ptr(ptr))
+}
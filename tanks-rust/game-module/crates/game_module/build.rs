@@ -2,22 +2,80 @@
 
 use ::std::{env, fs, path::Path};
 
-use syn::{FnArg, GenericArgument, Item, ItemFn, ItemStruct, PathArguments, Type};
+use syn::{
+    parse::ParseStream, Attribute, Fields, FieldsNamed, FnArg, GenericArgument, Ident, Item,
+    ItemFn, ItemMacro, ItemStruct, LitInt, PathArguments, Type, Visibility,
+};
 
 const ARETE_PUBLIC_COMPONENTS: &[&str] = &[
     "Camera",
     "Color",
     "DirectionalLight",
+    "Disabled",
     "DynamicStaticMesh",
+    "Instanced",
+    "Lifetime",
+    "Name",
+    "Parent",
     "PointLight",
+    "PreviousTransform",
+    "RenderLayer",
+    "RenderTarget",
     "Transform",
 ];
 
+/// The query callbacks which the engine provides via `set_callback_fn`, as
+/// `(CallbackType variant, static)` pairs. New callbacks must be appended, since the engine
+/// relies on the `CallbackType` discriminants. The static's function type is named by the
+/// variant with a leading underscore, e.g. `_QueryGetFn`.
+const CALLBACKS: &[(&str, &str)] = &[
+    ("QueryGetFn", "_QUERY_GET_FN"),
+    ("QueryGetMutFn", "_QUERY_GET_MUT_FN"),
+    ("QueryGetFirstFn", "_QUERY_GET_FIRST_FN"),
+    ("QueryGetFirstMutFn", "_QUERY_GET_FIRST_MUT_FN"),
+    ("QueryForEachFn", "_QUERY_FOR_EACH_FN"),
+    ("QueryParForEachFn", "_QUERY_PAR_FOR_EACH_FN"),
+    ("QueryForEachWithParentFn", "_QUERY_FOR_EACH_WITH_PARENT_FN"),
+    ("RemovedEntitiesFn", "_REMOVED_ENTITIES_FN"),
+    ("QueryForEachRevFn", "_QUERY_FOR_EACH_REV_FN"),
+    ("QueryForEachWithIdFn", "_QUERY_FOR_EACH_WITH_ID_FN"),
+    ("QueryAsSliceFn", "_QUERY_AS_SLICE_FN"),
+    ("QueryGetByStringIdFn", "_QUERY_GET_BY_STRING_ID_FN"),
+    ("QueryIncludeDisabledFn", "_QUERY_INCLUDE_DISABLED_FN"),
+    ("QueryForEachArchetypeFn", "_QUERY_FOR_EACH_ARCHETYPE_FN"),
+];
+
+/// The field types supported by component reflection, as `(type, FieldKind variant)` pairs.
+const FIELD_KINDS: &[(&str, &str)] = &[
+    ("f32", "F32"),
+    ("f64", "F64"),
+    ("i32", "I32"),
+    ("u32", "U32"),
+    ("i64", "I64"),
+    ("u64", "U64"),
+    ("bool", "Bool"),
+    ("Vec3", "Vec3"),
+    ("Quat", "Quat"),
+    ("EntityId", "EntityId"),
+    ("AssetId", "AssetId"),
+];
+
+/// The `# Safety` section of the generated functions which take a component's string id.
+const STRING_ID_SAFETY: &str =
+    "///\n/// # Safety\n///\n/// `string_id` must point to a valid nul-terminated string.\n";
+
 fn main() {
+    println!("cargo:rerun-if-changed=build.rs");
+    println!("cargo:rerun-if-changed=src/lib.rs");
+    println!("cargo:rerun-if-changed=../arete_public/src/lib.rs");
+
     let out_dir = env::var_os("OUT_DIR").unwrap();
     let dest_path = Path::new(&out_dir).join("ffi.rs");
 
-    let input = env::current_dir().unwrap().join("src/lib.rs");
+    let manifest_dir = env::var_os("CARGO_MANIFEST_DIR").unwrap();
+    let manifest_dir = Path::new(&manifest_dir);
+
+    let input = manifest_dir.join("src/lib.rs");
     let input = fs::read_to_string(input).unwrap();
     let file = syn::parse_file(&input).unwrap();
 
@@ -27,10 +85,34 @@ fn main() {
         match item {
             Item::Fn(item) => parsed_info.parse_fn(item),
             Item::Struct(item) => parsed_info.parse_struct(item),
+            Item::Macro(item) => parsed_info.parse_macro(item),
             _ => {}
         }
     }
 
+    parsed_info.add_event_systems();
+
+    // public components which opt out of `component_init` with `#[component(no_default)]`
+
+    let arete_public = manifest_dir.join("../arete_public/src/lib.rs");
+    let arete_public = fs::read_to_string(arete_public).unwrap();
+    let arete_public = syn::parse_file(&arete_public).unwrap();
+
+    let mut arete_public_info = ParsedInfo::default();
+
+    for item in &arete_public.items {
+        if let Item::Struct(item) = item {
+            arete_public_info.parse_struct(item);
+        }
+    }
+
+    parsed_info.arete_public_no_default = arete_public_info
+        .structs
+        .into_iter()
+        .filter(|s| s.no_default)
+        .map(|s| s.ident)
+        .collect();
+
     fs::write(dest_path, parsed_info.gen_ffi()).unwrap();
 }
 
@@ -43,13 +125,24 @@ enum StructType {
 #[derive(Debug)]
 enum ArgType {
     DataAccessDirect,
-    Query { inputs: Vec<SystemInputInfo> },
+    Query {
+        inputs: Vec<SystemInputInfo>,
+    },
+    Removed,
+    /// `With<T>` in a query, which filters entities without accessing the component.
+    With,
+    /// `&EntityId` in a query, which the engine provides directly rather than from component storage.
+    EntityId,
 }
 
 #[derive(Debug, Default)]
 struct ParsedInfo {
     systems: Vec<SystemInfo>,
     structs: Vec<StructInfo>,
+    events: Vec<String>,
+    errors: Vec<String>,
+    /// Public components with `#[component(no_default)]`, parsed from arete_public.
+    arete_public_no_default: Vec<String>,
 }
 
 #[derive(Debug)]
@@ -70,6 +163,17 @@ struct SystemInputInfo {
 struct StructInfo {
     ident: String,
     struct_type: StructType,
+    fields: Vec<FieldInfo>,
+    /// The network id pinned with `#[component(id = ..)]`.
+    network_id: Option<u16>,
+    /// Opted out of `component_init` with `#[component(no_default)]`.
+    no_default: bool,
+}
+
+#[derive(Debug)]
+struct FieldInfo {
+    ident: String,
+    kind: &'static str,
 }
 
 impl ParsedInfo {
@@ -93,8 +197,6 @@ impl ParsedInfo {
 
             let system_input = match input.ty.as_ref() {
                 Type::Path(component) => {
-                    // Query
-
                     let PathArguments::AngleBracketed(query_inputs) =
                         &component.path.segments.last().unwrap().arguments
                     else {
@@ -103,7 +205,27 @@ impl ParsedInfo {
 
                     let param_type = component.path.segments.last().unwrap().ident.to_string();
 
-                    let inputs = query_inputs
+                    if param_type == "Removed" {
+                        // Removed components
+
+                        let Some(GenericArgument::Type(Type::Path(component))) =
+                            query_inputs.args.first()
+                        else {
+                            panic!("invalid removed generics")
+                        };
+
+                        inputs.push(SystemInputInfo {
+                            ident: component.path.segments.last().unwrap().ident.to_string(),
+                            arg_type: ArgType::Removed,
+                            mutable: false,
+                        });
+
+                        continue;
+                    }
+
+                    // Query
+
+                    let mut inputs: Vec<SystemInputInfo> = query_inputs
                         .args
                         .iter()
                         .flat_map(|input| {
@@ -111,56 +233,25 @@ impl ParsedInfo {
                                 panic!("invalid query generics")
                             };
 
-                            if let Type::Reference(ty) = input {
-                                let Type::Path(component) = ty.elem.as_ref() else {
-                                    panic!("unsupported query input type")
-                                };
-
-                                Vec::from([SystemInputInfo {
-                                    ident: component
-                                        .path
-                                        .segments
-                                        .last()
-                                        .unwrap()
-                                        .ident
-                                        .to_string(),
-                                    arg_type: ArgType::DataAccessDirect,
-                                    mutable: ty.mutability.is_some(),
-                                }])
+                            if let Type::Tuple(tuple) = input {
+                                tuple.elems.iter().map(parse_query_input).collect()
                             } else {
-                                let Type::Tuple(tuple) = input else {
-                                    panic!("unsupported query input type")
-                                };
-
-                                tuple
-                                    .elems
-                                    .iter()
-                                    .map(|elem| {
-                                        let Type::Reference(ty) = elem else {
-                                            panic!("system inputs must be references")
-                                        };
-
-                                        let Type::Path(component) = ty.elem.as_ref() else {
-                                            panic!("unsupported system input type")
-                                        };
-
-                                        SystemInputInfo {
-                                            ident: component
-                                                .path
-                                                .segments
-                                                .last()
-                                                .unwrap()
-                                                .ident
-                                                .to_string(),
-                                            arg_type: ArgType::DataAccessDirect,
-                                            mutable: ty.mutability.is_some(),
-                                        }
-                                    })
-                                    .collect()
+                                Vec::from([parse_query_input(input)])
                             }
                         })
                         .collect();
 
+                    for input in inputs.iter_mut().filter(|input| input.ident == "EntityId") {
+                        if input.mutable {
+                            self.errors.push(format!(
+                                "system `{}` queries `&mut EntityId`; entity ids are read-only",
+                                item.sig.ident
+                            ));
+                        }
+
+                        input.arg_type = ArgType::EntityId;
+                    }
+
                     SystemInputInfo {
                         ident: param_type,
                         arg_type: ArgType::Query { inputs },
@@ -192,6 +283,8 @@ impl ParsedInfo {
             inputs.push(system_input);
         }
 
+        self.check_query_access(&item.sig.ident.to_string(), &inputs);
+
         self.systems.push(SystemInfo {
             ident: item.sig.ident.to_string(),
             is_once: is_system_once,
@@ -233,20 +326,151 @@ impl ParsedInfo {
             return;
         };
 
+        // only named fields of supported types are reflected, other fields are skipped
+
+        let fields = match &item.fields {
+            Fields::Named(fields) => fields
+                .named
+                .iter()
+                .filter_map(|field| {
+                    Some(FieldInfo {
+                        ident: field.ident.as_ref()?.to_string(),
+                        kind: field_kind(&field.ty)?,
+                    })
+                })
+                .collect(),
+            _ => Vec::new(),
+        };
+
+        let mut network_id = None;
+        let mut no_default = false;
+
+        for attr in item
+            .attrs
+            .iter()
+            .filter(|attr| attr.path().is_ident("component"))
+        {
+            attr.parse_nested_meta(|meta| {
+                if meta.path.is_ident("id") {
+                    network_id = Some(meta.value()?.parse::<LitInt>()?.base10_parse()?);
+                } else if meta.path.is_ident("no_default") {
+                    no_default = true;
+                } else {
+                    meta.parse_nested_meta(|_| Ok(()))?;
+                }
+
+                Ok(())
+            })
+            .expect("invalid component attribute");
+        }
+
         self.structs.push(StructInfo {
             ident: item.ident.to_string(),
             struct_type,
+            fields,
+            network_id,
+            no_default,
         });
     }
 
+    fn parse_macro(&mut self, item: &ItemMacro) {
+        if !item.mac.path.is_ident("define_event") {
+            return;
+        }
+
+        let ident = item
+            .mac
+            .parse_body_with(|input: ParseStream| {
+                input.call(Attribute::parse_outer)?;
+                input.parse::<Visibility>()?;
+                let ident = input.parse::<Ident>()?;
+                input.parse::<FieldsNamed>()?;
+                Ok(ident)
+            })
+            .expect("invalid define_event!() input");
+
+        // `define_event!(HitEvent { .. })` defines the `HitEvents` resource
+
+        let events = ident.to_string() + "s";
+
+        self.structs.push(StructInfo {
+            ident: events.clone(),
+            struct_type: StructType::Resource,
+            fields: Vec::new(),
+            network_id: None,
+            no_default: false,
+        });
+
+        self.events.push(events);
+    }
+
+    /// Adds the systems which swap each event resource's buffers. These are added after all
+    /// other systems, so that events sent during a frame are readable on the next frame.
+    fn add_event_systems(&mut self) {
+        for events in &self.events {
+            self.systems.push(SystemInfo {
+                ident: format!("_{}_update", events.to_lowercase()),
+                is_once: false,
+                inputs: Vec::from([SystemInputInfo {
+                    ident: events.clone(),
+                    arg_type: ArgType::DataAccessDirect,
+                    mutable: true,
+                }]),
+            });
+        }
+    }
+
+    /// Queries may match the same entities, so a component accessed mutably by one query
+    /// cannot be accessed by any other query (or twice by the same query) in one system.
+    fn check_query_access(&mut self, system: &str, inputs: &[SystemInputInfo]) {
+        let components: Vec<&SystemInputInfo> = inputs
+            .iter()
+            .filter_map(|input| match &input.arg_type {
+                ArgType::Query { inputs } => Some(inputs),
+                _ => None,
+            })
+            .flatten()
+            // `With<T>` filters and `&EntityId` don't access component data
+            .filter(|input| matches!(input.arg_type, ArgType::DataAccessDirect))
+            .collect();
+
+        let mut reported = Vec::new();
+
+        for (i, a) in components.iter().enumerate() {
+            if reported.contains(&a.ident) {
+                continue;
+            }
+
+            let conflict = components[i + 1..]
+                .iter()
+                .any(|b| a.ident == b.ident && (a.mutable || b.mutable));
+
+            if conflict {
+                self.errors.push(format!(
+                    "system `{system}` accesses `{}` mutably in one query and again in another; \
+                     split it into separate systems, passing data between them through a resource",
+                    a.ident
+                ));
+                reported.push(a.ident.clone());
+            }
+        }
+    }
+
     fn gen_ffi(self) -> String {
         let mut output = String::new();
 
+        for error in &self.errors {
+            output += &format!("compile_error!({error:?});\n");
+        }
+
         output += &gen_version();
         output += &self.gen_components();
         output += &self.gen_resource_init();
+        output += &self.gen_component_init();
         output += &self.gen_systems();
         output += &self.gen_callbacks();
+        output += &self.gen_module_ready();
+        output += &gen_take_par_for_each_panic();
 
         output
     }
@@ -260,18 +484,250 @@ impl ParsedInfo {
         output += "    Resource,\n";
         output += "}\n\n";
 
+        output += "/// The type of a reflected component field (see `component_field_kind`).\n";
         output += "#[repr(C)]\n";
-        output += "pub enum ArgType {\n";
-        output += "    DataAccessMut,\n";
-        output += "    DataAccessRef,\n";
-        output += "    Query,\n";
+        output += "pub enum FieldKind {\n";
+
+        for (_, kind) in FIELD_KINDS {
+            output += &format!("    {kind},\n");
+        }
+
         output += "}\n\n";
 
         output += &self.gen_component_string_id();
         output += &self.gen_component_size();
         output += &self.gen_component_align();
+        output += &self.gen_component_layout_hash();
         output += &self.gen_component_type();
         output += &self.gen_set_component_ids();
+        output += &self.gen_component_fields();
+        output += &self.gen_component_requires();
+        output += &self.gen_component_network_id();
+
+        output
+    }
+
+    /// Assigns each component type a network id. Pinned ids are kept, and the remaining
+    /// components take the lowest free ids in alphabetical order.
+    fn network_ids(&self) -> Vec<(String, u16)> {
+        let mut components: Vec<_> = self
+            .structs
+            .iter()
+            .filter(|s| matches!(s.struct_type, StructType::Component))
+            .map(|s| (s.ident.clone(), s.network_id))
+            .chain(
+                ARETE_PUBLIC_COMPONENTS
+                    .iter()
+                    .map(|s| (s.to_string(), None)),
+            )
+            .collect();
+
+        components.sort_unstable();
+
+        let pinned: Vec<_> = components.iter().filter_map(|(_, id)| *id).collect();
+        let mut next_id = 0..;
+
+        components
+            .into_iter()
+            .map(|(ident, network_id)| {
+                let network_id =
+                    network_id.unwrap_or_else(|| next_id.find(|id| !pinned.contains(id)).unwrap());
+
+                (ident, network_id)
+            })
+            .collect()
+    }
+
+    fn gen_component_network_id(&self) -> String {
+        let network_ids = self.network_ids();
+
+        let mut output = String::new();
+
+        for (i, (ident, network_id)) in network_ids.iter().enumerate() {
+            if let Some((other, _)) = network_ids[i + 1..].iter().find(|(_, id)| id == network_id) {
+                output += &format!(
+                    "compile_error!(\"components `{ident}` and `{other}` have the same network id {network_id}\");\n"
+                );
+            }
+        }
+
+        output += "/// Returns the network id of the component (see `Component::network_id()`).\n";
+        output += STRING_ID_SAFETY;
+        output += "#[no_mangle]\n";
+        output += "pub unsafe extern \"C\" fn component_network_id(string_id: *const ::std::ffi::c_char) -> u16 {\n";
+        output += "    let string_id = ::std::ffi::CStr::from_ptr(string_id);\n\n";
+
+        for (ident, network_id) in &network_ids {
+            output += &format!("    if string_id == {ident}::string_id() {{\n");
+            output += &format!("        return {network_id};\n");
+            output += "    }\n\n";
+        }
+
+        output += "    ::std::process::abort()\n";
+        output += "}\n\n";
+
+        output
+    }
+
+    fn gen_component_requires(&self) -> String {
+        let components: Vec<_> = self
+            .structs
+            .iter()
+            .filter(|s| matches!(s.struct_type, StructType::Component))
+            .map(|s| s.ident.as_str())
+            .chain(ARETE_PUBLIC_COMPONENTS.iter().copied())
+            .collect();
+
+        let mut output = String::new();
+
+        output += "/// Returns the string id of the required component at `index` (see `Component::requires()`),\n";
+        output += "/// or null if out of range.\n";
+        output += STRING_ID_SAFETY;
+        output += "#[no_mangle]\n";
+        output += "pub unsafe extern \"C\" fn component_requires(string_id: *const ::std::ffi::c_char, index: usize) -> *const ::std::ffi::c_char {\n";
+        output += "    let string_id = ::std::ffi::CStr::from_ptr(string_id);\n\n";
+        output += "    let requires = ";
+
+        for ident in &components {
+            output += &format!("if string_id == {ident}::string_id() {{\n");
+            output += &format!("        {ident}::requires()\n");
+            output += "    } else ";
+        }
+
+        output += "{\n";
+        output += "        ::std::process::abort()\n";
+        output += "    };\n\n";
+
+        output += "    match requires.get(index) {\n";
+        output += "        Some(string_id) => string_id().as_ptr(),\n";
+        output += "        None => ::std::ptr::null(),\n";
+        output += "    }\n";
+        output += "}\n\n";
+
+        output
+    }
+
+    /// Generates the component reflection functions. Only fields with a type listed in
+    /// `FIELD_KINDS` are reflected; fields of any other type (including tuple struct fields)
+    /// are invisible to reflection.
+    fn gen_component_fields(&self) -> String {
+        let mut output = String::new();
+
+        let kinds: Vec<_> = FIELD_KINDS
+            .iter()
+            .map(|(ty, _)| format!("`{ty}`"))
+            .collect();
+
+        output += "/// Returns the number of reflected fields of the component.\n";
+        output += "///\n";
+        output += &format!(
+            "/// Only named fields of type {} are reflected. Other fields are skipped.\n",
+            kinds.join(", ")
+        );
+        output += STRING_ID_SAFETY;
+        output += "#[no_mangle]\n";
+        output += "pub unsafe extern \"C\" fn component_field_count(string_id: *const ::std::ffi::c_char) -> usize {\n";
+        output += &self.gen_string_id_dispatch(|struct_info| {
+            format!("        {}\n", struct_info.fields.len())
+        });
+        output += "}\n\n";
+
+        output += "/// Returns the name of the reflected field at `field_index`, or null if out of range.\n";
+        output += STRING_ID_SAFETY;
+        output += "#[no_mangle]\n";
+        output += "pub unsafe extern \"C\" fn component_field_name(string_id: *const ::std::ffi::c_char, field_index: usize) -> *const ::std::ffi::c_char {\n";
+        output += &self.gen_string_id_dispatch(|struct_info| {
+            gen_field_match(
+                struct_info,
+                |field| format!("c\"{}\".as_ptr()", field.ident),
+                "::std::ptr::null()",
+            )
+        });
+        output += "}\n\n";
+
+        output += "/// Returns the byte offset of the reflected field at `field_index` within the component.\n";
+        output += STRING_ID_SAFETY;
+        output += "#[no_mangle]\n";
+        output += "pub unsafe extern \"C\" fn component_field_offset(string_id: *const ::std::ffi::c_char, field_index: usize) -> usize {\n";
+        output += &self.gen_string_id_dispatch(|struct_info| {
+            gen_field_match(
+                struct_info,
+                |field| {
+                    format!(
+                        "::std::mem::offset_of!({}, {})",
+                        struct_info.ident, field.ident
+                    )
+                },
+                "::std::process::abort()",
+            )
+        });
+        output += "}\n\n";
+
+        output += "/// Returns the type of the reflected field at `field_index`.\n";
+        output += STRING_ID_SAFETY;
+        output += "#[no_mangle]\n";
+        output += "pub unsafe extern \"C\" fn component_field_kind(string_id: *const ::std::ffi::c_char, field_index: usize) -> FieldKind {\n";
+        output += &self.gen_string_id_dispatch(|struct_info| {
+            gen_field_match(
+                struct_info,
+                |field| format!("FieldKind::{}", field.kind),
+                "::std::process::abort()",
+            )
+        });
+        output += "}\n\n";
+
+        output
+    }
+
+    /// Generates a function body which runs `body` for the struct matching `string_id`, or
+    /// aborts if no struct matches. Structs with the same body share a branch, and structs whose
+    /// body only aborts fall through to the final branch.
+    fn gen_string_id_dispatch(&self, body: impl Fn(&StructInfo) -> String) -> String {
+        const ABORT: &str = "        ::std::process::abort()\n";
+
+        let mut branches: Vec<(String, Vec<&str>)> = Vec::new();
+
+        for struct_info in &self.structs {
+            let body = body(struct_info);
+
+            if body == ABORT {
+                continue;
+            }
+
+            match branches.iter_mut().find(|(other, _)| *other == body) {
+                Some((_, idents)) => idents.push(&struct_info.ident),
+                None => branches.push((body, vec![&struct_info.ident])),
+            }
+        }
+
+        let mut output = String::new();
+
+        output += "    let string_id = ::std::ffi::CStr::from_ptr(string_id);\n\n";
+
+        for (i, (body, idents)) in branches.iter().enumerate() {
+            if i == 0 {
+                output += "    if ";
+            } else {
+                output += "    } else if ";
+            }
+
+            let conditions: Vec<_> = idents
+                .iter()
+                .map(|ident| format!("string_id == {ident}::string_id()"))
+                .collect();
+
+            output += &conditions.join(" || ");
+            output += " {\n";
+            output += body;
+        }
+
+        if branches.is_empty() {
+            output += "    ::std::process::abort()\n";
+        } else {
+            output += "    } else {\n";
+            output += ABORT;
+            output += "    }\n";
+        }
 
         output
     }
@@ -280,7 +736,7 @@ impl ParsedInfo {
         let mut output = String::new();
 
         output += "#[no_mangle]\n";
-        output += "pub unsafe extern \"C\" fn component_string_id(index: usize) -> *const ::std::ffi::c_char {\n";
+        output += "pub extern \"C\" fn component_string_id(index: usize) -> *const ::std::ffi::c_char {\n";
         output += "    match index {\n";
 
         for (i, struct_info) in self.structs.iter().enumerate() {
@@ -300,6 +756,8 @@ impl ParsedInfo {
     fn gen_component_size(&self) -> String {
         let mut output = String::new();
 
+        output += "/// Returns the size of the component or resource in bytes.\n";
+        output += STRING_ID_SAFETY;
         output += "#[no_mangle]\n";
         output += "pub unsafe extern \"C\" fn component_size(string_id: *const ::std::ffi::c_char) -> usize {\n";
         output += "    let string_id = ::std::ffi::CStr::from_ptr(string_id);\n\n";
@@ -336,6 +794,8 @@ impl ParsedInfo {
     fn gen_component_align(&self) -> String {
         let mut output = String::new();
 
+        output += "/// Returns the alignment of the component or resource in bytes.\n";
+        output += STRING_ID_SAFETY;
         output += "#[no_mangle]\n";
         output += "pub unsafe extern \"C\" fn component_align(string_id: *const ::std::ffi::c_char) -> usize {\n";
         output += "    let string_id = ::std::ffi::CStr::from_ptr(string_id);\n\n";
@@ -369,40 +829,113 @@ impl ParsedInfo {
         output
     }
 
+    /// Generates `component_layout_hash`, which lets the engine detect a component whose layout
+    /// differs from the one it cached (i.e. a stale build) before it copies any component data.
+    fn gen_component_layout_hash(&self) -> String {
+        let mut output = String::new();
+
+        output +=
+            "/// Returns a hash of the component's size, alignment, and reflected fields (see\n";
+        output += "/// `arete_public::layout_hash`).\n";
+        output += "///\n";
+        output += "/// The field offsets come from the reflection metadata (see `component_field_count`), so\n";
+        output += "/// changes to fields which are not reflected are only detected through the size and\n";
+        output += "/// alignment.\n";
+        output += STRING_ID_SAFETY;
+        output += "#[no_mangle]\n";
+        output += "pub unsafe extern \"C\" fn component_layout_hash(string_id: *const ::std::ffi::c_char) -> u64 {\n";
+        output += &self.gen_string_id_dispatch(|struct_info| {
+            let ident = &struct_info.ident;
+
+            let mut words = vec![
+                format!("::std::mem::size_of::<{ident}>() as u64"),
+                format!("::std::mem::align_of::<{ident}>() as u64"),
+            ];
+
+            for field in &struct_info.fields {
+                words.push(format!(
+                    "::std::mem::offset_of!({ident}, {}) as u64",
+                    field.ident
+                ));
+                words.push(format!("FieldKind::{} as u64", field.kind));
+            }
+
+            let mut output = String::new();
+
+            output += "        const HASH: u64 = ::arete_public::layout_hash(&[\n";
+
+            for word in words {
+                output += &format!("            {word},\n");
+            }
+
+            output += "        ]);\n";
+            output += "        HASH\n";
+
+            output
+        });
+        output += "}\n\n";
+
+        output
+    }
+
     fn gen_component_type(&self) -> String {
         let mut output = String::new();
 
+        output += "/// Returns whether the string id names a component or a resource.\n";
+        output += STRING_ID_SAFETY;
         output += "#[no_mangle]\n";
         output += "pub unsafe extern \"C\" fn component_type(string_id: *const ::std::ffi::c_char) -> ComponentType {\n";
+        output += &self.gen_string_id_dispatch(|struct_info| match struct_info.struct_type {
+            StructType::Component => "        ComponentType::Component\n".to_string(),
+            StructType::Resource => "        ComponentType::Resource\n".to_string(),
+        });
+        output += "}\n\n";
+
+        output
+    }
+
+    fn gen_set_component_ids(&self) -> String {
+        let components = self.component_idents();
+        let network_ids = self.network_ids();
+
+        let mut output = String::new();
+
+        // `_COMPONENT_ID_SET` tracks which ids have been set, for `module_ready`
+
+        output += &format!(
+            "static _COMPONENT_ID_SET: [::std::sync::atomic::AtomicBool; {}] = [\n",
+            components.len()
+        );
+
+        for _ in &components {
+            output += "    ::std::sync::atomic::AtomicBool::new(false),\n";
+        }
+
+        output += "];\n\n";
+
+        output += "/// Sets the id which the engine assigned to the component or resource.\n";
+        output += STRING_ID_SAFETY;
+        output += "#[no_mangle]\n";
+        output += "pub unsafe extern \"C\" fn set_component_id(string_id: *const ::std::ffi::c_char, id: ComponentId) {\n";
         output += "    let string_id = ::std::ffi::CStr::from_ptr(string_id);\n\n";
 
-        if self.structs.is_empty() {
-            output += "    ::std::process::abort()\n";
-        } else {
-            output += &format!(
-                "    if string_id == {}::string_id() {{\n",
-                self.structs[0].ident
-            );
-            output += "        ComponentType::";
-            output += match &self.structs[0].struct_type {
-                StructType::Component => "Component\n",
-                StructType::Resource => "Resource\n",
-            };
+        for (i, ident) in components.iter().enumerate() {
+            if i == 0 {
+                output += &format!("    if string_id == {ident}::string_id() {{\n");
+            } else {
+                output += &format!("    }} else if string_id == {ident}::string_id() {{\n");
+            }
 
-            for struct_info in &self.structs[1..] {
-                output += &format!(
-                    "    }} else if string_id == {}::string_id() {{\n",
-                    struct_info.ident
-                );
-                output += "        ComponentType::";
-                output += match &struct_info.struct_type {
-                    StructType::Component => "Component\n",
-                    StructType::Resource => "Resource\n",
-                };
+            output += &format!("        {ident}::set_id(id);\n");
+
+            if let Some((_, network_id)) = network_ids.iter().find(|(i, _)| i == ident) {
+                output += &format!("        {ident}::set_network_id({network_id});\n");
             }
 
-            output += "    } else {\n";
-            output += "        ::std::process::abort()\n";
+            output += &format!("        _COMPONENT_ID_SET[{i}].store(true, ::std::sync::atomic::Ordering::Relaxed);\n");
+        }
+
+        if !components.is_empty() {
             output += "    }\n";
         }
 
@@ -411,7 +944,8 @@ impl ParsedInfo {
         output
     }
 
-    fn gen_set_component_ids(&self) -> String {
+    /// Every component and resource whose id is set by the engine via `set_component_id`.
+    fn component_idents(&self) -> Vec<String> {
         let mut components: Vec<_> = self
             .systems
             .iter()
@@ -430,24 +964,55 @@ impl ParsedInfo {
         components.sort_unstable();
         components.dedup();
 
+        components
+    }
+
+    fn gen_component_init(&self) -> String {
+        let components: Vec<_> = self
+            .structs
+            .iter()
+            .filter(|s| matches!(s.struct_type, StructType::Component) && !s.no_default)
+            .map(|s| s.ident.as_str())
+            .chain(
+                ARETE_PUBLIC_COMPONENTS
+                    .iter()
+                    .filter(|s| !self.arete_public_no_default.iter().any(|n| n == *s))
+                    .copied(),
+            )
+            .collect();
+
         let mut output = String::new();
 
+        output += "/// Writes the default value of the component to `val`, i.e. for the editor to add the\n";
+        output +=
+            "/// component to an entity. Returns 1 if the component is unknown or opted out with\n";
+        output += "/// `#[component(no_default)]`.\n";
+        output += "///\n";
+        output += "/// # Safety\n";
+        output += "///\n";
+        output += "/// `string_id` must point to a valid nul-terminated string, and `val` must be valid for\n";
+        output += "/// writes of the component.\n";
         output += "#[no_mangle]\n";
-        output += "pub unsafe extern \"C\" fn set_component_id(string_id: *const ::std::ffi::c_char, id: ComponentId) {\n";
+        output += "pub unsafe extern \"C\" fn component_init(\n";
+        output += "    string_id: *const ::std::ffi::c_char,\n";
+        output += "    val: *mut ::std::ffi::c_void,\n";
+        output += ") -> i32 {\n";
         output += "    let string_id = ::std::ffi::CStr::from_ptr(string_id);\n\n";
 
-        if !components.is_empty() {
-            output += &format!("    if string_id == {}::string_id() {{\n", components[0]);
-            output += &format!("        {}::set_id(id);\n", components[0]);
-
-            for ident in &components[1..] {
+        for (i, ident) in components.iter().enumerate() {
+            if i == 0 {
+                output += &format!("    if string_id == {ident}::string_id() {{\n");
+            } else {
                 output += &format!("    }} else if string_id == {ident}::string_id() {{\n");
-                output += &format!("        {ident}::set_id(id);\n");
             }
 
-            output += "    }\n";
+            output += &format!("        (val as *mut {ident}).write(Default::default());\n");
         }
 
+        output += "    } else {\n";
+        output += "        return 1;\n";
+        output += "    }\n\n";
+        output += "    0\n";
         output += "}\n\n";
 
         output
@@ -463,6 +1028,12 @@ impl ParsedInfo {
 
         let mut output = String::new();
 
+        output += "/// Writes the default value of the resource to `val`. Returns 1 if the resource is unknown.\n";
+        output += "///\n";
+        output += "/// # Safety\n";
+        output += "///\n";
+        output += "/// `string_id` must point to a valid nul-terminated string, and `val` must be valid for\n";
+        output += "/// writes of the resource.\n";
         output += "#[no_mangle]\n";
         output += "pub unsafe extern \"C\" fn resource_init(\n";
         output += "    string_id: *const ::std::ffi::c_char,\n";
@@ -497,9 +1068,12 @@ impl ParsedInfo {
     fn gen_systems(&self) -> String {
         let mut output = String::new();
 
+        output += &self.gen_system_did_run();
+        output += &gen_bump_query_epoch();
         output += &self.gen_system_fn_ffi();
         output += &self.gen_systems_len();
         output += &self.gen_system_is_once();
+        output += &self.gen_system_name();
         output += &self.gen_system_fn();
         output += &self.gen_system_args_len();
         output += &self.gen_system_arg_type();
@@ -515,10 +1089,11 @@ impl ParsedInfo {
     fn gen_system_fn_ffi(&self) -> String {
         let mut output = String::new();
 
-        let gen_system_fn = &mut |system: &SystemInfo| {
+        let gen_system_fn = &mut |system_index: usize, system: &SystemInfo| {
             output += "unsafe extern \"C\" fn ";
             output += &system.ident;
             output += "_ffi(data: *mut *mut ::std::ffi::c_void) -> i32 {\n";
+            output += &format!("    _SYSTEM_RAN[{system_index}].store(true, ::std::sync::atomic::Ordering::Relaxed);\n\n");
             output += "    ::std::panic::catch_unwind(|| {\n";
             output += "        ";
             output += &system.ident;
@@ -527,6 +1102,8 @@ impl ParsedInfo {
             for (i, input) in system.inputs.iter().enumerate() {
                 if let ArgType::Query { .. } = &input.arg_type {
                     output += &format!("            Query::new(*data.offset({i})),\n");
+                } else if let ArgType::Removed = &input.arg_type {
+                    output += &format!("            Removed::new(*data.offset({i})),\n");
                 } else {
                     output += "            &";
 
@@ -554,13 +1131,53 @@ impl ParsedInfo {
             output += "}\n\n";
         };
 
-        for system in &self.systems {
-            gen_system_fn(system);
+        for (i, system) in self.systems.iter().enumerate() {
+            gen_system_fn(i, system);
         }
 
         output
     }
 
+    fn gen_system_did_run(&self) -> String {
+        let mut output = String::new();
+
+        // `_SYSTEM_RAN` is set as systems run, then copied to `_SYSTEM_DID_RUN` at frame end
+
+        for name in ["_SYSTEM_RAN", "_SYSTEM_DID_RUN"] {
+            output += &format!(
+                "static {name}: [::std::sync::atomic::AtomicBool; {}] = [\n",
+                self.systems.len()
+            );
+
+            for _ in &self.systems {
+                output += "    ::std::sync::atomic::AtomicBool::new(false),\n";
+            }
+
+            output += "];\n\n";
+        }
+
+        output += "/// Called by the engine at the end of each frame.\n";
+        output += "#[no_mangle]\n";
+        output += "pub extern \"C\" fn system_did_run_update() {\n";
+        output += "    for (ran, did_run) in _SYSTEM_RAN.iter().zip(&_SYSTEM_DID_RUN) {\n";
+        output += "        did_run.store(ran.swap(false, ::std::sync::atomic::Ordering::Relaxed), ::std::sync::atomic::Ordering::Relaxed);\n";
+        output += "    }\n";
+        output += "}\n\n";
+
+        output += "/// Returns `true` if the system at `system_index` ran on the most recent frame. Earlier\n";
+        output += "/// frames are not tracked.\n";
+        output += "#[no_mangle]\n";
+        output += "pub extern \"C\" fn system_did_run(system_index: usize) -> bool {\n";
+        output += "    match _SYSTEM_DID_RUN.get(system_index) {\n";
+        output +=
+            "        Some(did_run) => did_run.load(::std::sync::atomic::Ordering::Relaxed),\n";
+        output += "        None => ::std::process::abort(),\n";
+        output += "    }\n";
+        output += "}\n\n";
+
+        output
+    }
+
     fn gen_systems_len(&self) -> String {
         let mut output = String::new();
 
@@ -572,6 +1189,26 @@ impl ParsedInfo {
         output
     }
 
+    fn gen_system_name(&self) -> String {
+        let mut output = String::new();
+
+        output += "/// Returns the name of the system function at `system_index`.\n";
+        output += "#[no_mangle]\n";
+        output +=
+            "pub extern \"C\" fn system_name(system_index: usize) -> *const ::std::ffi::c_char {\n";
+        output += "    match system_index {\n";
+
+        for (i, system) in self.systems.iter().enumerate() {
+            output += &format!("        {i} => c\"{}\".as_ptr(),\n", system.ident);
+        }
+
+        output += "        _ => ::std::process::abort(),\n";
+        output += "    }\n";
+        output += "}\n\n";
+
+        output
+    }
+
     fn gen_system_is_once(&self) -> String {
         let mut output = String::new();
 
@@ -642,6 +1279,9 @@ impl ParsedInfo {
                     ArgType::DataAccessDirect if input.mutable => "DataAccessMut,\n",
                     ArgType::DataAccessDirect => "DataAccessRef,\n",
                     ArgType::Query { .. } => "Query,\n",
+                    ArgType::Removed => "Removed,\n",
+                    ArgType::EntityId => "EntityId,\n",
+                    ArgType::With => "With,\n",
                 };
             }
 
@@ -664,14 +1304,23 @@ impl ParsedInfo {
         output += "    match system_index {\n";
 
         for (i, system) in self.systems.iter().enumerate() {
-            output += &format!("        {i} => match arg_index {{\n");
-
-            for (i, input) in system
+            let inputs: Vec<_> = system
                 .inputs
                 .iter()
                 .enumerate()
-                .filter(|(_, input)| matches!(input.arg_type, ArgType::DataAccessDirect))
-            {
+                .filter(|(_, input)| {
+                    matches!(input.arg_type, ArgType::DataAccessDirect | ArgType::Removed)
+                })
+                .collect();
+
+            if inputs.is_empty() {
+                output += &format!("        {i} => ::std::process::abort(),\n");
+                continue;
+            }
+
+            output += &format!("        {i} => match arg_index {{\n");
+
+            for (i, input) in inputs {
                 output += &format!(
                     "            {i} => {}::string_id().as_ptr(),\n",
                     input.ident
@@ -745,10 +1394,12 @@ impl ParsedInfo {
                     output += &format!("            {i} => match query_index {{\n");
 
                     for (i, input) in inputs.iter().enumerate() {
-                        output += &format!("                {i} => ArgType::DataAccess");
-                        output += match input.mutable {
-                            true => "Mut,\n",
-                            false => "Ref,\n",
+                        output += &format!("                {i} => ArgType::");
+                        output += match &input.arg_type {
+                            ArgType::EntityId => "EntityId,\n",
+                            ArgType::With => "With,\n",
+                            _ if input.mutable => "DataAccessMut,\n",
+                            _ => "DataAccessRef,\n",
                         };
                     }
 
@@ -778,40 +1429,6 @@ impl ParsedInfo {
         output += "    query_index: usize,\n";
         output += ") -> *const ::std::ffi::c_char {\n";
 
-        // SAFETY: verify tuple layout
-
-        for query_inputs in self.systems.iter().flat_map(|system| {
-            system
-                .inputs
-                .iter()
-                .filter_map(|input| match &input.arg_type {
-                    ArgType::Query { inputs } if inputs.len() > 1 => Some(inputs),
-                    _ => None,
-                })
-        }) {
-            output += "    let layout_check: (";
-
-            for input in query_inputs {
-                output += if input.mutable { "*mut " } else { "*const " };
-                output += &input.ident;
-                output += ", ";
-            }
-
-            output += ") = (";
-
-            for _ in query_inputs {
-                output += "::std::ptr::null_mut(), ";
-            }
-
-            output += ");\n";
-
-            for i in 1..query_inputs.len() {
-                output += &format!("    assert_eq!(&layout_check.{} as *const _ as usize - &layout_check.{} as *const _ as usize, ::std::mem::size_of::<*const ::std::ffi::c_void>());\n", i, i - 1);
-            }
-
-            output += "\n";
-        }
-
         output += "    match system_index {\n";
 
         for (i, system) in self.systems.iter().enumerate().filter(|(_, system)| {
@@ -827,10 +1444,15 @@ impl ParsedInfo {
                     output += &format!("            {i} => match query_index {{\n");
 
                     for (i, input) in inputs.iter().enumerate() {
-                        output += &format!(
-                            "                {i} => {}::string_id().as_ptr(),\n",
-                            input.ident
-                        );
+                        if let ArgType::EntityId = &input.arg_type {
+                            // not a stored component, so there is no string id
+                            output += &format!("                {i} => ::std::ptr::null(),\n");
+                        } else {
+                            output += &format!(
+                                "                {i} => {}::string_id().as_ptr(),\n",
+                                input.ident
+                            );
+                        }
                     }
 
                     output += "                _ => ::std::process::abort(),\n";
@@ -853,49 +1475,201 @@ impl ParsedInfo {
         let mut output = String::new();
 
         output += "#[repr(C)]\npub enum CallbackType {\n";
-        output += "    QueryGetFn,\n";
-        output += "    QueryGetMutFn,\n";
-        output += "    QueryGetFirstFn,\n";
-        output += "    QueryGetFirstMutFn,\n";
-        output += "    QueryForEachFn,\n";
-        output += "    QueryParForEachFn,\n";
+
+        for (variant, _) in CALLBACKS {
+            output += &format!("    {variant},\n");
+        }
+
         output += "}\n\n";
 
+        output += "/// Sets the engine function backing a query callback.\n";
+        output += "///\n";
+        output += "/// # Safety\n";
+        output += "///\n";
+        output += "/// `callback` must be null or point to a function with the signature which the static for\n";
+        output += "/// `callback_type` expects.\n";
         output += "#[no_mangle]\npub unsafe extern \"C\" fn set_callback_fn(\n";
         output += "    callback_type: CallbackType,\n";
         output += "    callback: *const ::std::ffi::c_void\n";
         output += ") {\n";
         output += "    match callback_type {\n";
-        output += "        CallbackType::QueryGetFn => {\n";
-        output += "            _QUERY_GET_FN = ::std::mem::transmute(callback);\n";
-        output += "        }\n";
-        output += "        CallbackType::QueryGetMutFn => {\n";
-        output += "            _QUERY_GET_MUT_FN = ::std::mem::transmute(callback);\n";
-        output += "        }\n";
-        output += "        CallbackType::QueryGetFirstFn => {\n";
-        output += "            _QUERY_GET_FIRST_FN = ::std::mem::transmute(callback);\n";
-        output += "        }\n";
-        output += "        CallbackType::QueryGetFirstMutFn => {\n";
-        output += "            _QUERY_GET_FIRST_MUT_FN = ::std::mem::transmute(callback);\n";
-        output += "        }\n";
-        output += "        CallbackType::QueryForEachFn => {\n";
-        output += "            _QUERY_FOR_EACH_FN = ::std::mem::transmute(callback);\n";
-        output += "        }\n";
-        output += "        CallbackType::QueryParForEachFn => {\n";
-        output += "            _QUERY_PAR_FOR_EACH_FN = ::std::mem::transmute(callback);\n";
-        output += "        }\n";
+
+        for (variant, callback) in CALLBACKS {
+            output += &format!("        CallbackType::{variant} => {{\n");
+            output += &format!(
+                "            {callback} = ::std::mem::transmute::<*const ::std::ffi::c_void, Option<_{variant}>>(callback);\n"
+            );
+            output += "        }\n";
+        }
+
         output += "    }\n";
         output += "}\n\n";
 
         output
     }
+
+    fn gen_module_ready(&self) -> String {
+        let mut output = String::new();
+
+        output += "/// Returns `true` once every callback has been set with `set_callback_fn`, and every\n";
+        output +=
+            "/// component id has been set with `set_component_id`. The engine must not run any\n";
+        output += "/// systems until the module is ready.\n";
+        output += "#[no_mangle]\n";
+        output += "pub extern \"C\" fn module_ready() -> bool {\n";
+        output += "    let callbacks = unsafe {\n";
+        output += "        (\n";
+
+        for (_, callback) in CALLBACKS {
+            output += &format!("            {callback},\n");
+        }
+
+        output += "        )\n";
+        output += "    };\n\n";
+
+        output += "    if !matches!(callbacks, (";
+        output += &vec!["Some(_)"; CALLBACKS.len()].join(", ");
+        output += ")) {\n";
+        output += "        return false;\n";
+        output += "    }\n\n";
+
+        output += "    _COMPONENT_ID_SET\n";
+        output += "        .iter()\n";
+        output += "        .all(|set| set.load(::std::sync::atomic::Ordering::Relaxed))\n";
+        output += "}\n\n";
+
+        output
+    }
+}
+
+/// Generates a `match` on `field_index`, with one arm per reflected field of the struct, falling
+/// back to `fallback` for out of range indices.
+fn gen_field_match(
+    struct_info: &StructInfo,
+    arm: impl Fn(&FieldInfo) -> String,
+    fallback: &str,
+) -> String {
+    if struct_info.fields.is_empty() {
+        return format!("        {fallback}\n");
+    }
+
+    let mut output = String::new();
+
+    output += "        match field_index {\n";
+
+    for (i, field) in struct_info.fields.iter().enumerate() {
+        output += &format!("            {i} => {},\n", arm(field));
+    }
+
+    output += &format!("            _ => {fallback},\n");
+    output += "        }\n";
+
+    output
+}
+
+/// Parses one element of a query template: a component reference, or a `With<T>` filter.
+fn parse_query_input(ty: &Type) -> SystemInputInfo {
+    match ty {
+        Type::Reference(ty) => {
+            let Type::Path(component) = ty.elem.as_ref() else {
+                panic!("unsupported query input type")
+            };
+
+            SystemInputInfo {
+                ident: component.path.segments.last().unwrap().ident.to_string(),
+                arg_type: ArgType::DataAccessDirect,
+                mutable: ty.mutability.is_some(),
+            }
+        }
+        Type::Path(filter) if filter.path.segments.last().unwrap().ident == "With" => {
+            let PathArguments::AngleBracketed(args) =
+                &filter.path.segments.last().unwrap().arguments
+            else {
+                panic!("invalid `With` generics")
+            };
+
+            let Some(GenericArgument::Type(Type::Path(component))) = args.args.first() else {
+                panic!("invalid `With` generics")
+            };
+
+            SystemInputInfo {
+                ident: component.path.segments.last().unwrap().ident.to_string(),
+                arg_type: ArgType::With,
+                mutable: false,
+            }
+        }
+        _ => panic!("query inputs must be references or `With<T>` filters"),
+    }
+}
+
+/// Returns the `FieldKind` variant of a field type, or `None` if the type can't be reflected.
+fn field_kind(ty: &Type) -> Option<&'static str> {
+    let Type::Path(ty) = ty else {
+        return None;
+    };
+
+    let ident = ty.path.get_ident()?;
+
+    FIELD_KINDS
+        .iter()
+        .find(|(ty, _)| ident == ty)
+        .map(|(_, kind)| *kind)
+}
+
+fn gen_bump_query_epoch() -> String {
+    let mut output = String::new();
+
+    output += "/// Called by the engine whenever no system is running, invalidating the queries passed to\n";
+    output += "/// previous systems. Using a stale query panics in debug builds.\n";
+    output += "#[no_mangle]\n";
+    output += "pub extern \"C\" fn bump_query_epoch() {\n";
+    output += "    ::arete_public::_bump_query_epoch();\n";
+    output += "}\n\n";
+
+    output
+}
+
+fn gen_take_par_for_each_panic() -> String {
+    let mut output = String::new();
+
+    output += "/// Copies the details of the first panic caught in `Query::par_for_each` since the last call,\n";
+    output += "/// so the engine can log the panicking system, entity index, and message. The message is\n";
+    output += "/// truncated to fit in `message_capacity` bytes, including the nul terminator.\n";
+    output += "///\n";
+    output += "/// Returns `false` if no panic was caught.\n";
+    output += "///\n";
+    output += "/// # Safety\n";
+    output += "///\n";
+    output +=
+        "/// `entity_index` must be valid for writes, and `message` must be valid for writes of\n";
+    output += "/// `message_capacity` bytes.\n";
+    output += "#[no_mangle]\n";
+    output += "pub unsafe extern \"C\" fn take_par_for_each_panic(\n";
+    output += "    entity_index: *mut usize,\n";
+    output += "    message: *mut ::std::ffi::c_char,\n";
+    output += "    message_capacity: usize,\n";
+    output += ") -> bool {\n";
+    output += "    let Some(panic) = ::arete_public::_take_par_for_each_panic() else {\n";
+    output += "        return false;\n";
+    output += "    };\n\n";
+    output += "    *entity_index = panic.entity_index;\n\n";
+    output += "    if message_capacity > 0 {\n";
+    output += "        let len = panic.message.len().min(message_capacity - 1);\n";
+    output +=
+        "        ::std::ptr::copy_nonoverlapping(panic.message.as_ptr(), message.cast(), len);\n";
+    output += "        *message.add(len) = 0;\n";
+    output += "    }\n\n";
+    output += "    true\n";
+    output += "}\n\n";
+
+    output
 }
 
 fn gen_version() -> String {
     let mut output = String::new();
 
     output += "#[no_mangle]\n";
-    output += "pub unsafe extern \"C\" fn arete_target_version() -> u32 {\n";
+    output += "pub extern \"C\" fn arete_target_version() -> u32 {\n";
     output += "    ::arete_public::ENGINE_VERSION\n";
     output += "}\n\n";
 
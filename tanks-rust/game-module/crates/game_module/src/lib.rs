@@ -15,19 +15,21 @@ use noise::{NoiseFn, Perlin};
 //
 // A struct must implement `Component` to be used as such. As a convenience, you can
 // simply `#[derive(Component)]` to automatically implement `Component` on a struct.
+//
+// Components must also implement Default, which is used when adding a component in the editor.
 
-#[derive(Component)]
+#[derive(Component, Default)]
 pub struct Velocity {
     val: Vec3,
 }
 
-#[derive(Component)]
+#[derive(Component, Default)]
 pub struct PlayerTank {
     /// The current direction the player tank is facing
     angle: f32,
 }
 
-#[derive(Component)]
+#[derive(Component, Default)]
 pub struct AiTank {
     /// This id seeds the noise function used for movement
     id: u32,
@@ -62,6 +64,8 @@ fn spawn_tanks(engine: &Engine) {
     };
 
     // spawn player tank
+    //
+    // All tanks share the same mesh, so we tag them as `Instanced` to draw them in one batch.
 
     let color = &Color {
         val: Vec3::new(1.0, 0.0, 0.0),
@@ -76,11 +80,26 @@ fn spawn_tanks(engine: &Engine) {
         color,
         mesh,
         point_light,
+        &Instanced,
         &PlayerTank { angle: 0.0 },
         &Transform::default(),
     ));
 
     // spawn AI tanks
+    //
+    // Reserving storage up front avoids growing it repeatedly while spawning.
+
+    engine.reserve(
+        &[
+            Color::id(),
+            DynamicStaticMesh::id(),
+            PointLight::id(),
+            Instanced::id(),
+            AiTank::id(),
+            Transform::id(),
+        ],
+        19,
+    );
 
     for id in 1..20 {
         let color = &Color {
@@ -96,6 +115,7 @@ fn spawn_tanks(engine: &Engine) {
             color,
             mesh,
             point_light,
+            &Instanced,
             &AiTank { id },
             &Transform::default()
         ));
@@ -195,10 +215,15 @@ fn ai_tank_update(
             .get([seed.x as f64, tank.id as f64, seed.z as f64]) as f32;
         let angle = (0.5 + noise) * 4.0 * PI;
 
-        let tank_direction = Vec3::new(angle.sin(), 0.0, angle.cos());
+        // Turn towards the target angle at a limited rate, so the tank turns smoothly.
+
+        let target_rotation = glm::quat_angle_axis(angle, &glm::Vec3::y()).into();
 
-        transform.position += tank_direction * frame_constants.delta_time * 5.0;
-        transform.rotation = glm::quat_angle_axis(angle, &glm::Vec3::y()).into();
+        transform.rotation = transform
+            .rotation
+            .rotate_towards(target_rotation, frame_constants.delta_time * 4.0);
+
+        transform.position += transform.forward() * frame_constants.delta_time * 5.0;
 
         // Shoot one cannonball per frame.
 
@@ -231,10 +256,11 @@ fn player_tank_update(
         // touch input
 
         if let Some(touch) = input.touches().next() {
-            // touch.position.x is in range [0, 1]. (0.5 - touch.position.x) * 2.0 gives us a value in
-            // range [-1, 1], and the extra .2 gives us a margin with max input on the sides of the screen.
-            let input_val = ((0.5 - touch.position.x) * 2.2).clamp(-1.0, 1.0);
-            tank.angle += frame_constants.delta_time * input_val * 2.0;
+            // touch.position.x is in range [0, 1]. Dividing the offset from the center by slightly
+            // less than half the screen gives us a margin with max input on the sides of the screen.
+            // Only the horizontal position steers, so the turn rate doesn't depend on the height.
+            let input_val = ((touch.position.x - 0.5) / 0.45).clamp(-1.0, 1.0);
+            tank.angle -= frame_constants.delta_time * input_val * 2.0;
         }
 
         // Calculate direction from angle and orient tank.
@@ -243,7 +269,7 @@ fn player_tank_update(
 
         // Check forward/back (W/S) input
 
-        let tank_direction = Vec3::new(tank.angle.sin(), 0.0, tank.angle.cos());
+        let tank_direction = transform.forward();
 
         if input.key_w.pressed {
             transform.position += tank_direction * frame_constants.delta_time * 5.0;
@@ -257,6 +283,9 @@ fn player_tank_update(
 
         if input.key_space.pressed || input.touches_len > 0 {
             spawn_cannonball(engine, color, transform);
+
+            // A short rumble, renewed every frame while firing.
+            engine.set_gamepad_rumble(0, 0.2, 0.4, 0.05);
         }
     });
 }
@@ -265,39 +294,38 @@ fn player_tank_update(
 /// This function is NOT tagged with `#[system]`, so it is not included in frame processing.
 fn spawn_cannonball(engine: &Engine, color: &Color, tank_transform: &Transform) {
     // Shoot from the tip of the cannon, which is (0.0, 1.235, 0.324) in local coordinates
-    let position_offset_glm = glm::quat_rotate_vec(
-        &tank_transform.rotation,
-        &glm::Vec4::new(0.0, 1.235, 0.324, 0.0),
-    )
-    .xyz();
-
     let transform = &Transform {
-        position: tank_transform.position + position_offset_glm.into(),
+        position: tank_transform.transform_point(Vec3::new(0.0, 1.235, 0.324)),
         rotation: tank_transform.rotation,
         scale: Vec3::new(0.2, 0.2, 0.2),
     };
 
-    let velocity_glm =
-        glm::quat_rotate_vec(&transform.rotation, &glm::Vec4::new(0.0, 0.717, 0.8, 0.0)) * 20.0;
-
     let velocity = &Velocity {
-        val: velocity_glm.xyz().into(),
+        val: transform.transform_direction(Vec3::new(0.0, 0.717, 0.8)) * 20.0,
     };
 
     let mesh = &DynamicStaticMesh {
         asset_id: engine.load_asset(c_str!("sphere.glb")),
     };
 
-    engine.spawn(bundle!(transform, color, mesh, velocity));
+    // Cannonballs are despawned once they stop bouncing, but we also give them a lifetime,
+    // so that the engine cleans up any which keep bouncing for too long.
+
+    let lifetime = &Lifetime { remaining: 10.0 };
+
+    engine.spawn(bundle!(
+        transform, color, mesh, velocity, lifetime, &Instanced
+    ));
 }
 
 #[system]
 fn cannonball_update(
-    mut query: Query<(&mut Transform, &mut Velocity, &EntityId)>,
+    mut query: Query<(&mut Transform, &mut Velocity, &Color, &EntityId)>,
+    camera_target: &CameraTarget,
     frame_constants: &FrameConstants,
     engine: &Engine,
 ) {
-    query.par_for_each(|(transform, velocity, entity_id)| {
+    query.par_for_each(|(transform, velocity, color, entity_id)| {
         // Move cannonball by the current velocity.
 
         transform.position += velocity.val * frame_constants.delta_time;
@@ -307,6 +335,27 @@ fn cannonball_update(
         if transform.position.y < 0.1 {
             transform.position.y += 0.1 - transform.position.y;
 
+            // Kick up a few sparks on impact. The engine simulates and despawns these for us.
+
+            engine.spawn_particles(ParticleBurst {
+                mesh: engine.load_asset(c_str!("sphere.glb")),
+                count: 4,
+                origin: transform.position,
+                velocity_spread: Vec3::new(2.0, 2.0, 2.0),
+                color: color.val,
+                scale: 0.05,
+                lifetime: 0.5,
+                queryable: false,
+            });
+
+            // Shake the camera when a cannonball lands close to the player tank.
+
+            if let Some(target) = &camera_target.transform {
+                if (transform.position - target.position).norm_squared() < 4.0 {
+                    engine.add_trauma(0.1);
+                }
+            }
+
             let damping = Vec3::new(0.8, -0.8, 0.8);
             velocity.val *= damping;
         }
@@ -331,12 +380,26 @@ fn point_light_update(mut query: Query<(&Transform, &mut PointLight)>) {
     });
 }
 
+// A system may not access a component mutably in one query and immutably in another, since
+// both queries could match the same entity. Instead, we split the camera update in two: the
+// first system copies the player tank transform into a resource, and the second reads it.
+
+#[derive(Resource, Default)]
+pub struct CameraTarget {
+    transform: Option<Transform>,
+}
+
 #[system]
-fn camera_update(
-    mut query_camera: Query<(&Camera, &mut Transform)>,
+fn camera_target_update(
     query_player_tank: Query<(&PlayerTank, &Transform)>,
+    target: &mut CameraTarget,
 ) {
-    let Some(tank_transform) = query_player_tank.get_first::<Transform>() else {
+    target.transform = query_player_tank.get_first::<Transform>().copied();
+}
+
+#[system]
+fn camera_update(mut query_camera: Query<(&Camera, &mut Transform)>, target: &CameraTarget) {
+    let Some(tank_transform) = &target.transform else {
         return;
     };
 
@@ -348,23 +411,10 @@ fn camera_update(
 fn camera_transform(tank_transform: &Transform) -> Transform {
     // Position the camera above and behind the player tank.
 
-    let camera_local_position =
-        glm::quat_rotate_vec3(&tank_transform.rotation, &glm::Vec3::new(0.0, 5.0, -10.0));
-
-    let position = tank_transform.position + camera_local_position.into();
-    let direction = tank_transform.position + Vec3::y() - position;
+    let position =
+        tank_transform.position + tank_transform.transform_direction(Vec3::new(0.0, 5.0, -10.0));
 
-    // glm::quat_look_at seems bugged, need to invert the quaternion
-    let rotation = glm::quat_look_at(&direction.into(), &glm::Vec3::y())
-        .try_inverse()
-        .unwrap()
-        .into();
-
-    Transform {
-        position,
-        rotation,
-        ..Default::default()
-    }
+    camera_look_at(position, tank_transform.position + Vec3::y(), Vec3::y())
 }
 
 // This includes auto-generated C FFI code (saves you from writing it manually).
@@ -1,27 +1,80 @@
 //! READ ONLY.
 //!
 //! This crate provides the proc macros which allow you to derive the `Component` and `Resource` traits,
-//! as well as the `#[system]` and `#[system_once]` attributes.
+//! as well as the `#[system]` and `#[system_once]` attributes and the `define_event!()` macro.
 
 extern crate proc_macro;
 use proc_macro::TokenStream;
 use proc_macro2::Span;
 use quote::quote;
-use syn::{parse_macro_input, DeriveInput, Ident, LitStr};
-
-#[proc_macro_derive(Component)]
+use syn::{
+    parse::{Parse, ParseStream},
+    parse_macro_input, Attribute, DeriveInput, FieldsNamed, Ident, LitInt, LitStr, Visibility,
+};
+
+/// Derives `Component`.
+///
+/// Required components are declared with `#[component(requires(A, B))]` (see `Component::requires()`),
+/// and the network id may be pinned with `#[component(id = 7)]` (see `Component::network_id()`).
+/// Components must implement `Default`, unless they opt out with `#[component(no_default)]`.
+#[proc_macro_derive(Component, attributes(component))]
 pub fn derive_component(input: TokenStream) -> TokenStream {
-    let DeriveInput { ident, .. } = parse_macro_input!(input);
+    let DeriveInput { ident, attrs, .. } = parse_macro_input!(input);
+
+    let mut requires = Vec::new();
+
+    for attr in attrs
+        .iter()
+        .filter(|attr| attr.path().is_ident("component"))
+    {
+        let result = attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("requires") {
+                meta.parse_nested_meta(|meta| {
+                    requires.push(meta.path);
+                    Ok(())
+                })
+            } else if meta.path.is_ident("id") {
+                // the id is assigned by the FFI codegen, this only validates it
+                meta.value()?.parse::<LitInt>()?.base10_parse::<u16>()?;
+                Ok(())
+            } else if meta.path.is_ident("no_default") {
+                // only read by the FFI codegen
+                Ok(())
+            } else {
+                Err(meta.error("expected `requires(..)`, `id = ..`, or `no_default`"))
+            }
+        });
+
+        if let Err(err) = result {
+            return err.to_compile_error().into();
+        }
+    }
+
+    let requires_fn = if requires.is_empty() {
+        quote!()
+    } else {
+        quote!(
+            fn requires() -> &'static [fn() -> &'static ::core::ffi::CStr] {
+                &[#(<#requires as Component>::string_id),*]
+            }
+        )
+    };
 
     let cid = Ident::new(
         &("_".to_string() + &ident.to_string().to_uppercase() + "_CID"),
         Span::call_site(),
     );
 
+    let nid = Ident::new(
+        &("_".to_string() + &ident.to_string().to_uppercase() + "_NID"),
+        Span::call_site(),
+    );
+
     let sid = LitStr::new(&ident.to_string(), Span::call_site());
 
     quote!(
         static mut #cid: ComponentId = 0;
+        static mut #nid: u16 = 0;
 
         impl Component for #ident {
             fn id() -> ComponentId {
@@ -34,9 +87,21 @@ pub fn derive_component(input: TokenStream) -> TokenStream {
                 }
             }
 
-            fn string_id() -> &'static std::ffi::CStr {
-                unsafe { std::ffi::CStr::from_bytes_with_nul_unchecked(concat!(module_path!(), "::", #sid, "\0").as_bytes()) }
+            fn network_id() -> u16 {
+                unsafe { #nid }
+            }
+
+            fn set_network_id(id: u16) {
+                unsafe {
+                    #nid = id;
+                }
+            }
+
+            fn string_id() -> &'static ::core::ffi::CStr {
+                unsafe { ::core::ffi::CStr::from_bytes_with_nul_unchecked(concat!(module_path!(), "::", #sid, "\0").as_bytes()) }
             }
+
+            #requires_fn
         }
 
         impl Copy for #ident {}
@@ -54,6 +119,10 @@ pub fn derive_component(input: TokenStream) -> TokenStream {
 pub fn derive_resource(input: TokenStream) -> TokenStream {
     let DeriveInput { ident, .. } = parse_macro_input!(input);
 
+    impl_resource(&ident).into()
+}
+
+fn impl_resource(ident: &Ident) -> proc_macro2::TokenStream {
     let cid = Ident::new(
         &("_".to_string() + &ident.to_string().to_uppercase() + "_CID"),
         Span::call_site(),
@@ -79,10 +148,89 @@ pub fn derive_resource(input: TokenStream) -> TokenStream {
                 }
             }
 
-            fn string_id() -> &'static std::ffi::CStr {
-                unsafe { std::ffi::CStr::from_bytes_with_nul_unchecked(concat!(module_path!(), "::", #sid, "\0").as_bytes()) }
+            fn string_id() -> &'static ::core::ffi::CStr {
+                unsafe { ::core::ffi::CStr::from_bytes_with_nul_unchecked(concat!(module_path!(), "::", #sid, "\0").as_bytes()) }
+            }
+        }
+    )
+}
+
+struct EventInput {
+    attrs: Vec<Attribute>,
+    vis: Visibility,
+    ident: Ident,
+    fields: FieldsNamed,
+}
+
+impl Parse for EventInput {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        Ok(Self {
+            attrs: input.call(Attribute::parse_outer)?,
+            vis: input.parse()?,
+            ident: input.parse()?,
+            fields: input.parse()?,
+        })
+    }
+}
+
+/// Defines an event type, along with a resource holding its `Events` channel.
+///
+/// `define_event!(HitEvent { pub damage: f32 })` defines the `HitEvent` struct and the
+/// `HitEvents` resource, which dereferences to `Events<HitEvent>`. Systems send events with
+/// `&HitEvents` and read them with `&mut HitEvents`.
+///
+/// The FFI codegen also adds a system which swaps the event buffers once per frame.
+#[proc_macro]
+pub fn define_event(input: TokenStream) -> TokenStream {
+    let EventInput {
+        attrs,
+        vis,
+        ident,
+        fields,
+    } = parse_macro_input!(input);
+
+    let events = Ident::new(&(ident.to_string() + "s"), Span::call_site());
+
+    let update_fn = Ident::new(
+        &("_".to_string() + &events.to_string().to_lowercase() + "_update"),
+        Span::call_site(),
+    );
+
+    let doc = LitStr::new(
+        &format!("The `Events` resource for `{ident}`."),
+        Span::call_site(),
+    );
+
+    let resource = impl_resource(&events);
+
+    quote!(
+        #(#attrs)*
+        #[derive(Clone, Copy)]
+        #vis struct #ident #fields
+
+        #[doc = #doc]
+        #[derive(Default)]
+        #vis struct #events(::arete_public::Events<#ident>);
+
+        impl ::core::ops::Deref for #events {
+            type Target = ::arete_public::Events<#ident>;
+
+            fn deref(&self) -> &Self::Target {
+                &self.0
+            }
+        }
+
+        impl ::core::ops::DerefMut for #events {
+            fn deref_mut(&mut self) -> &mut Self::Target {
+                &mut self.0
             }
         }
+
+        #resource
+
+        fn #update_fn(events: &mut #events) {
+            events.update();
+        }
     )
     .into()
 }